@@ -0,0 +1,199 @@
+use crate::crystal::{Crystarium, NodeRole, NodeType};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub mod simulation;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Analysis {
+    pub character: String,
+    pub roles: Vec<RoleStats>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RoleStats {
+    pub role: String,
+    pub stages: Vec<StageStats>,
+    pub total_cp: i64,
+    pub hp: i64,
+    pub strength: i64,
+    pub magic: i64,
+    pub atb_nodes: usize,
+    pub accessory_nodes: usize,
+    pub ability_nodes: usize,
+    pub role_nodes: usize,
+    //CP spent on HP/STR/MAG nodes per gained point, None without stat nodes.
+    pub cp_per_hp: Option<f64>,
+    pub cp_per_strength: Option<f64>,
+    pub cp_per_magic: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StageStats {
+    pub stage: u8,
+    //CP to buy every node of the role in this stage.
+    pub cp: i64,
+    //Sums over this and all earlier stages.
+    pub cumulative_cp: i64,
+    pub cumulative_hp: i64,
+    pub cumulative_strength: i64,
+    pub cumulative_magic: i64,
+}
+
+#[derive(Default)]
+struct StatCost {
+    points: i64,
+    cp: i64,
+}
+
+impl StatCost {
+    fn add(&mut self, value: i16, cp_cost: i32) {
+        self.points += i64::from(value);
+        self.cp += i64::from(cp_cost);
+    }
+
+    fn efficiency(&self) -> Option<f64> {
+        (self.points > 0).then(|| self.cp as f64 / self.points as f64)
+    }
+}
+
+fn role_stats(role: u8, crystarium: &Crystarium) -> RoleStats {
+    let mut stats = RoleStats {
+        role: NodeRole::from(role).to_string(),
+        ..Default::default()
+    };
+    let (mut hp, mut strength, mut magic) = (StatCost::default(), StatCost::default(), StatCost::default());
+
+    //Stages in order, so the running sums are the cumulative values.
+    let mut stages: BTreeMap<u8, StageStats> = BTreeMap::new();
+    for node in crystarium.nodes.iter().filter(|node| node.role == role) {
+        let stage = stages.entry(node.stage).or_insert_with(|| StageStats { stage: node.stage, ..Default::default() });
+        stage.cp += i64::from(node.cp_cost);
+
+        match node.node_type {
+            NodeType::HP => {
+                hp.add(node.node_value, node.cp_cost);
+                stage.cumulative_hp += i64::from(node.node_value);
+            }
+            NodeType::STR => {
+                strength.add(node.node_value, node.cp_cost);
+                stage.cumulative_strength += i64::from(node.node_value);
+            }
+            NodeType::MAG => {
+                magic.add(node.node_value, node.cp_cost);
+                stage.cumulative_magic += i64::from(node.node_value);
+            }
+            NodeType::ATB => stats.atb_nodes += 1,
+            NodeType::ACCESSORY => stats.accessory_nodes += 1,
+            NodeType::ABILITY => stats.ability_nodes += 1,
+            NodeType::ROLE => stats.role_nodes += 1,
+            NodeType::INVALID => (),
+        }
+    }
+
+    let mut previous = StageStats::default();
+    for (_, mut stage) in stages {
+        stage.cumulative_cp = previous.cumulative_cp + stage.cp;
+        stage.cumulative_hp += previous.cumulative_hp;
+        stage.cumulative_strength += previous.cumulative_strength;
+        stage.cumulative_magic += previous.cumulative_magic;
+
+        previous = stage.clone();
+        stats.stages.push(stage);
+    }
+
+    stats.total_cp = previous.cumulative_cp;
+    stats.hp = previous.cumulative_hp;
+    stats.strength = previous.cumulative_strength;
+    stats.magic = previous.cumulative_magic;
+    stats.cp_per_hp = hp.efficiency();
+    stats.cp_per_strength = strength.efficiency();
+    stats.cp_per_magic = magic.efficiency();
+
+    stats
+}
+
+pub fn analyze(crystarium: &Crystarium) -> Analysis {
+    let mut roles: Vec<u8> = crystarium.nodes.iter().map(|node| node.role).collect();
+    roles.sort();
+    roles.dedup();
+
+    Analysis {
+        character: crystarium.character.clone(),
+        roles: roles.into_iter().map(|role| role_stats(role, crystarium)).collect(),
+    }
+}
+
+#[test]
+fn test_analyze() {
+    use crate::crystal::Node;
+
+    let node = |stage: u8, role: NodeRole, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        stage,
+        role: role as u8,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node(1, NodeRole::COMMANDO, NodeType::HP, 20, 40),
+            node(1, NodeRole::COMMANDO, NodeType::STR, 5, 50),
+            node(2, NodeRole::COMMANDO, NodeType::HP, 30, 60),
+            node(2, NodeRole::COMMANDO, NodeType::ABILITY, 0, 100),
+            node(1, NodeRole::MEDIC, NodeType::MAG, 4, 80),
+        ],
+    };
+
+    let analysis = analyze(&crystarium);
+    let commando = &analysis.roles[0];
+    assert_eq!((commando.role.as_str(), commando.total_cp, commando.hp), ("COMMANDO", 250, 50));
+    assert_eq!(commando.stages[1].cumulative_cp, 250);
+    assert_eq!(commando.stages[1].cumulative_strength, 5);
+    assert_eq!(commando.cp_per_hp, Some(2.0));
+    assert_eq!(commando.cp_per_magic, None);
+    assert_eq!(commando.ability_nodes, 1);
+    assert_eq!(analysis.roles[1].cp_per_magic, Some(20.0));
+}
+
+#[test]
+fn test_analyze_cumulative() {
+    use crate::crystal::Node;
+
+    let node = |stage: u8, role: u8, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        stage,
+        role,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ..Default::default()
+    };
+    let commando = NodeRole::COMMANDO as u8;
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node(3, commando, NodeType::HP, 10, 30),
+            node(1, commando, NodeType::INVALID, 50, 70),
+            node(1, commando, NodeType::HP, 20, 30),
+            node(1, 9, NodeType::STR, 5, 10),
+        ],
+    };
+
+    let analysis = analyze(&crystarium);
+    assert!(analyze(&Crystarium::default()).roles.is_empty());
+
+    //Stages are summed in stage order, a stage without nodes of the role is skipped instead of repeated.
+    let commando = &analysis.roles[0];
+    let cumulative: Vec<(u8, i64, i64)> =
+        commando.stages.iter().map(|stage| (stage.stage, stage.cumulative_cp, stage.cumulative_hp)).collect();
+    assert_eq!(cumulative, vec![(1, 100, 20), (3, 130, 30)]);
+
+    //Unknown types cost CP but add no stat and don't count into the CP per point.
+    assert_eq!((commando.total_cp, commando.hp, commando.strength), (130, 30, 0));
+    assert_eq!(commando.cp_per_hp, Some(2.0));
+
+    //Roles outside of the known ones get their own entry.
+    assert_eq!((analysis.roles[1].role.as_str(), analysis.roles[1].strength), ("INVALID", 5));
+}
@@ -0,0 +1,232 @@
+use crate::crystal::{Crystarium, Node, NodeRole, NodeType};
+use crate::optimizer::{role_paths, Weights};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// Income file, TOML or the same fields as JSON ({"chapter": [...]})
+//     [[chapter]]
+//     name = "Chapter 1"
+//     cp = 1200                          CP earned in the battles of the chapter
+//     stage = 1                          last unlocked crystarium stage
+//     roles = ["COMMANDO", "RAVAGER"]    roles the character can level, every role if missing
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Income {
+    #[serde(rename = "chapter", alias = "chapters")]
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    pub name: String,
+    pub cp: i64,
+    pub stage: u8,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+//Which node is bought next. The chosen node is saved up for if the CP isn't enough, like a player would.
+#[derive(Debug, Clone)]
+pub enum Policy {
+    //Cheapest next node of all roles.
+    Cheapest,
+    //Roles in this order, a role is only leveled once the previous ones are done up to the unlocked stage.
+    //Roles which aren't listed are not leveled.
+    Roles(Vec<u8>),
+    //Best score per CP.
+    Weighted(Weights),
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ChapterReport {
+    pub name: String,
+    pub income: i64,
+    pub spent: i64,
+    //CP carried into the next chapter.
+    pub cp_left: i64,
+    //Nodes bought in this chapter.
+    pub bought: Vec<String>,
+    //Everything below is the state at the end of the chapter.
+    pub hp: i64,
+    pub strength: i64,
+    pub magic: i64,
+    pub atb_nodes: usize,
+    pub accessory_nodes: usize,
+    pub role_nodes: usize,
+    pub abilities: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Simulation {
+    pub character: String,
+    pub chapters: Vec<ChapterReport>,
+    //Nodes still missing after the last chapter.
+    pub remaining_nodes: usize,
+    pub remaining_cp: i64,
+}
+
+impl Income {
+    pub fn from_toml(text: &str) -> Result<Income, String> {
+        toml::from_str::<Income>(text).map_err(|err| err.to_string())?.checked()
+    }
+
+    pub fn from_json(text: &str) -> Result<Income, String> {
+        serde_json::from_str::<Income>(text).map_err(|err| err.to_string())?.checked()
+    }
+
+    fn checked(self) -> Result<Income, String> {
+        for chapter in self.chapters.iter() {
+            for role in chapter.roles.iter() {
+                NodeRole::from_str(role).map_err(|err| format!("{}: {}", chapter.name, err))?;
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+impl Chapter {
+    fn allows(&self, role: u8) -> bool {
+        self.roles.is_empty()
+            || self.roles.iter().any(|name| NodeRole::from_str(name).is_ok_and(|r| r as u8 == role))
+    }
+}
+
+impl Policy {
+    //Index of the path to buy from, out of the paths whose next node is available.
+    fn choose(&self, candidates: &[(usize, &Node)]) -> Option<usize> {
+        match self {
+            Policy::Cheapest => candidates.iter().min_by_key(|(_, node)| node.cp_cost).map(|(path, _)| *path),
+            Policy::Roles(order) => order
+                .iter()
+                .find_map(|role| candidates.iter().find(|(_, node)| node.role == *role))
+                .map(|(path, _)| *path),
+            Policy::Weighted(weights) => candidates
+                .iter()
+                .max_by(|(_, a), (_, b)| {
+                    let ratio = |node: &Node| weights.score(node) / f64::from(node.cp_cost.max(1));
+                    //Ties go to the earlier role.
+                    ratio(a).total_cmp(&ratio(b)).then(b.role.cmp(&a.role))
+                })
+                .map(|(path, _)| *path),
+        }
+    }
+}
+
+pub fn simulate(crystarium: &Crystarium, income: &Income, policy: &Policy) -> Simulation {
+    let paths = role_paths(crystarium, u8::MAX);
+    let mut next = vec![0; paths.len()];
+    let mut cp = 0;
+    let mut state = ChapterReport::default();
+
+    let mut simulation = Simulation { character: crystarium.character.clone(), ..Default::default() };
+    for chapter in income.chapters.iter() {
+        cp += chapter.cp;
+        state.bought.clear();
+        state.spent = 0;
+
+        loop {
+            let candidates: Vec<(usize, &Node)> = paths
+                .iter()
+                .enumerate()
+                .filter_map(|(path, nodes)| nodes.get(next[path]).map(|node| (path, *node)))
+                .filter(|(_, node)| node.stage <= chapter.stage && chapter.allows(node.role))
+                .collect();
+
+            let Some(path) = policy.choose(&candidates) else {
+                break;
+            };
+            let node = paths[path][next[path]];
+            if i64::from(node.cp_cost) > cp {
+                break;
+            }
+
+            next[path] += 1;
+            cp -= i64::from(node.cp_cost);
+            state.spent += i64::from(node.cp_cost);
+            state.bought.push(node.node_name.clone());
+
+            match node.node_type {
+                NodeType::HP => state.hp += i64::from(node.node_value),
+                NodeType::STR => state.strength += i64::from(node.node_value),
+                NodeType::MAG => state.magic += i64::from(node.node_value),
+                NodeType::ATB => state.atb_nodes += 1,
+                NodeType::ACCESSORY => state.accessory_nodes += 1,
+                NodeType::ROLE => state.role_nodes += 1,
+                NodeType::ABILITY => state.abilities.push(node.ability.clone()),
+                NodeType::INVALID => (),
+            }
+        }
+
+        simulation.chapters.push(ChapterReport {
+            name: chapter.name.clone(),
+            income: chapter.cp,
+            cp_left: cp,
+            ..state.clone()
+        });
+    }
+
+    simulation.remaining_nodes = paths.iter().zip(next.iter()).map(|(path, bought)| path.len() - bought).sum();
+    simulation.remaining_cp = paths
+        .iter()
+        .zip(next.iter())
+        .flat_map(|(path, bought)| path[*bought..].iter())
+        .map(|node| i64::from(node.cp_cost))
+        .sum();
+
+    simulation
+}
+
+#[test]
+fn test_simulate() {
+    let node = |name: &str, stage: u8, role: NodeRole, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        node_name: name.to_string(),
+        stage,
+        role: role as u8,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ability: match node_type {
+            NodeType::ABILITY => "ab_ruin".to_string(),
+            _ => String::new(),
+        },
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node("cr_lt0101000000", 1, NodeRole::COMMANDO, NodeType::STR, 5, 50),
+            node("cr_lt0102000000", 1, NodeRole::COMMANDO, NodeType::ABILITY, 0, 80),
+            node("cr_lt0201000000", 2, NodeRole::COMMANDO, NodeType::HP, 40, 100),
+            node("cr_lt0601000000", 1, NodeRole::MEDIC, NodeType::MAG, 3, 60),
+        ],
+    };
+
+    let income = Income::from_toml(
+        r#"
+        [[chapter]]
+        name = "Chapter 1"
+        cp = 200
+        stage = 1
+        roles = ["COMMANDO"]
+
+        [[chapter]]
+        name = "Chapter 2"
+        cp = 50
+        stage = 2
+        "#,
+    )
+    .unwrap();
+
+    let simulation = simulate(&crystarium, &income, &Policy::Cheapest);
+    let first = &simulation.chapters[0];
+    assert_eq!((first.spent, first.cp_left, first.strength, first.magic), (130, 70, 5, 0));
+    assert_eq!(first.abilities, vec!["ab_ruin".to_string()]);
+
+    //Stage 2 and the medic role open up, the stage 2 node has to wait for more CP.
+    let second = &simulation.chapters[1];
+    assert_eq!(second.bought, vec!["cr_lt0601000000".to_string()]);
+    assert_eq!((second.cp_left, second.magic, second.hp), (60, 3, 0));
+    assert_eq!((simulation.remaining_nodes, simulation.remaining_cp), (1, 100));
+
+    assert!(Income::from_json(r#"{"chapter": [{"name": "1", "cp": 10, "stage": 1, "roles": ["HEALER"]}]}"#).is_err());
+}
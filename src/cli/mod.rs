@@ -0,0 +1,750 @@
+use ff13_crystal_re::{
+    analysis::{
+        analyze,
+        simulation::{simulate, Income, Policy, Simulation},
+        Analysis,
+    },
+    crystal::{
+        read_crystal_wdb_with_file, read_crystal_wdb_with_structure, write_crystal_wdb, Crystarium, Layout, NodeRole,
+        NodeType,
+    },
+    diff::diff,
+    export,
+    optimizer::{optimize, Plan, Weights},
+    patch,
+    randomizer::{randomize, Options, Scope},
+    rebalance::{rebalance, Filter, Transform},
+    table::read_table,
+    validation::{self, Issue},
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "FF13CrystalRE", version, about = "Read, edit and write Final Fantasy XIII crystal_*.wdb files")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the web server (default without subcommand)
+    Serve {
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// Print every node of a crystal file
+    Dump { file: PathBuf },
+    /// Compare two crystal files node by node
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Print the differences as JSON
+        #[arg(long, conflicts_with = "patch")]
+        json: bool,
+        /// Print a TOML patch which turns the old file into the new one, see `apply`
+        #[arg(long)]
+        patch: bool,
+    },
+    /// Apply a TOML patch and write the result as new crystal file, nothing is written on a conflict
+    Apply {
+        file: PathBuf,
+        patch: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Export a crystal file to another format
+    Export {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the nodes of a crystal file with an exported file and write the result as new crystal file
+    Import {
+        file: PathBuf,
+        /// JSON, CSV or TSV written by `export` or the /export routes
+        input: PathBuf,
+        /// Format of the input, taken from its extension if not given
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Change fields of a single node and write the result as new crystal file, see `apply` for patch files
+    Edit {
+        file: PathBuf,
+        #[arg(long)]
+        node: String,
+        #[arg(long)]
+        cp_cost: Option<i32>,
+        #[arg(long)]
+        value: Option<i16>,
+        /// HP, STR, MAG, ACCESSORY, ATB, ABILITY or ROLE
+        #[arg(long = "type")]
+        node_type: Option<NodeType>,
+        #[arg(long)]
+        stage: Option<u8>,
+        /// COMMANDO, RAVAGER, SENTINEL, SABOTEUR, SYNERGIST or MEDIC
+        #[arg(long)]
+        role: Option<NodeRole>,
+        #[arg(long)]
+        ability: Option<String>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print CP and stat statistics per role and stage
+    Analyze {
+        file: PathBuf,
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Plan the node purchase order which gives the most weighted stats for a CP budget
+    Optimize {
+        file: PathBuf,
+        #[arg(long)]
+        budget: i64,
+        /// Last stage which is unlocked
+        #[arg(long, default_value_t = 15)]
+        max_stage: u8,
+        #[command(flatten)]
+        weights: WeightArgs,
+        /// Print the plan as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Buy nodes chapter by chapter with the CP of an income file and print the stats at the end of each chapter
+    Simulate {
+        file: PathBuf,
+        /// TOML or JSON file with name, cp, stage and optionally roles per chapter
+        income: PathBuf,
+        #[arg(long, value_enum, default_value_t = PolicyArg::Weighted)]
+        policy: PolicyArg,
+        /// Role order of the roles policy, e.g. COMMANDO,RAVAGER
+        #[arg(long, value_delimiter = ',')]
+        roles: Vec<NodeRole>,
+        #[command(flatten)]
+        weights: WeightArgs,
+        /// Print the simulation as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Scale CP costs and stat values of many nodes at once, prints the changes without --output
+    Rebalance {
+        file: PathBuf,
+        /// Multiply the CP costs, e.g. 0.5 halves them
+        #[arg(long)]
+        cost: Option<f64>,
+        /// Multiply the values of HP/STR/MAG nodes
+        #[arg(long)]
+        value: Option<f64>,
+        /// Upper limit of the values of HP/STR/MAG nodes
+        #[arg(long)]
+        cap: Option<i16>,
+        /// Only these stages, e.g. 1,2,3
+        #[arg(long, value_delimiter = ',')]
+        stage: Vec<u8>,
+        /// Only these roles, e.g. COMMANDO,MEDIC
+        #[arg(long, value_delimiter = ',')]
+        role: Vec<NodeRole>,
+        /// Only these node types, e.g. HP,STR
+        #[arg(long = "type", value_delimiter = ',')]
+        node_type: Vec<NodeType>,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Shuffle node types, values and abilities and write the result as new crystal file
+    Randomize {
+        file: PathBuf,
+        /// Same seed and options give the same crystal, taken from the clock if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, value_enum, default_value_t = ScopeArg::Role)]
+        scope: ScopeArg,
+        /// Don't move role level nodes
+        #[arg(long)]
+        keep_role_nodes: bool,
+        /// Keep the summed HP/STR/MAG of every stage
+        #[arg(long)]
+        keep_stage_budget: bool,
+        /// Keep ATB levels ascending by stage
+        #[arg(long)]
+        keep_atb_order: bool,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Write the spoiler log to this file (JSON for .json) instead of stdout
+        #[arg(long)]
+        spoiler: Option<PathBuf>,
+    },
+    /// Show the records of a WDB database without type lists as raw words
+    Table {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Tsv)]
+        format: ExportFormat,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that crystal files can be read and written back unchanged and report suspicious nodes
+    Validate {
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<PathBuf>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+//Weights of the optimizer and the weighted simulation policy.
+#[derive(Debug, Args)]
+pub struct WeightArgs {
+    /// Score per HP point
+    #[arg(long, default_value_t = 1.0)]
+    hp: f64,
+    /// Score per STR point
+    #[arg(long = "str", default_value_t = 1.0)]
+    strength: f64,
+    /// Score per MAG point
+    #[arg(long = "mag", default_value_t = 1.0)]
+    magic: f64,
+    /// Score per ability node
+    #[arg(long, default_value_t = 0.0)]
+    ability: f64,
+    /// Score per ATB node
+    #[arg(long, default_value_t = 0.0)]
+    atb: f64,
+    /// Score per accessory node
+    #[arg(long, default_value_t = 0.0)]
+    accessory: f64,
+    /// Score per role level node
+    #[arg(long, default_value_t = 0.0)]
+    role: f64,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PolicyArg {
+    /// Cheapest available node first
+    Cheapest,
+    /// Roles in the order of --roles
+    Roles,
+    /// Best weighted stats per CP
+    Weighted,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ScopeArg {
+    /// Shuffle between the stages of a role
+    Role,
+    /// Shuffle between the roles of a stage
+    Stage,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl ExportFormat {
+    fn from_extension(path: &Path) -> Result<ExportFormat, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ExportFormat::Json),
+            Some("csv") => Ok(ExportFormat::Csv),
+            Some("tsv") => Ok(ExportFormat::Tsv),
+            _ => Err(format!("{}: unknown format, use --format", path.display())),
+        }
+    }
+}
+
+impl From<WeightArgs> for Weights {
+    fn from(args: WeightArgs) -> Self {
+        Weights {
+            hp: args.hp,
+            strength: args.strength,
+            magic: args.magic,
+            ability: args.ability,
+            atb: args.atb,
+            accessory: args.accessory,
+            role: args.role,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FileReport {
+    file: String,
+    nodes: usize,
+    error: Option<String>,
+    issues: Vec<Issue>,
+}
+
+impl FileReport {
+    fn is_ok(&self) -> bool {
+        self.error.is_none() && self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(err) => write!(f, "{}: {}", self.file, err)?,
+            None if self.issues.is_empty() => write!(f, "{}: ok ({} nodes)", self.file, self.nodes)?,
+            None => write!(f, "{}: {} issues ({} nodes)", self.file, self.issues.len(), self.nodes)?,
+        }
+
+        for issue in self.issues.iter() {
+            write!(f, "\n  {}", issue)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str, String> {
+    path.to_str().ok_or_else(|| format!("{}: path is not valid UTF-8", path.display()))
+}
+
+fn write_output(output: &Option<PathBuf>, content: &str) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(path, content).map_err(|err| format!("{}: {}", path.display(), err)),
+        //Writing through stdout directly, a closed pipe (e.g. `| head`) is an error instead of a panic.
+        None => writeln!(io::stdout(), "{}", content).map_err(|err| err.to_string()),
+    }
+}
+
+pub fn dump(layout: &dyn Layout, crystarium: &Crystarium) -> String {
+    let mut lines = vec![
+        format!("{} ({} nodes, {} layout)", crystarium.character, crystarium.nodes.len(), layout.name()),
+        format!(
+            "{:<16} {:>5} {:<12} {:<12} {:>6} {:>6}  {}",
+            "NAME", "STAGE", "ROLE", "TYPE", "VALUE", "COST", "ABILITY"
+        ),
+    ];
+
+    for node in crystarium.nodes.iter() {
+        //Fields of other layouts follow the ability, e.g. [byte_12=5].
+        let extra = match node.extra.is_empty() {
+            true => String::new(),
+            false => {
+                let fields: Vec<String> = node.extra.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+                format!(" [{}]", fields.join(" "))
+            }
+        };
+
+        lines.push(format!(
+            "{:<16} {:>5} {:<12} {:<12} {:>6} {:>6}  {}{}",
+            node.node_name,
+            node.stage,
+            node.role_label(),
+            node.type_label(),
+            node.node_value,
+            node.cp_cost,
+            node.ability,
+            extra
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn efficiency(cp: Option<f64>) -> String {
+    cp.map_or("-".to_string(), |cp| format!("{:.1}", cp))
+}
+
+pub fn analysis_text(analysis: &Analysis) -> String {
+    let mut lines = vec![
+        analysis.character.clone(),
+        format!(
+            "{:<10} {:>8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}  {:>3} {:>3} {:>3} {:>3}",
+            "ROLE", "CP", "HP", "STR", "MAG", "CP/HP", "CP/STR", "CP/MAG", "ATB", "ACC", "ABL", "ROL"
+        ),
+    ];
+
+    for role in analysis.roles.iter() {
+        lines.push(format!(
+            "{:<10} {:>8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}  {:>3} {:>3} {:>3} {:>3}",
+            role.role,
+            role.total_cp,
+            role.hp,
+            role.strength,
+            role.magic,
+            efficiency(role.cp_per_hp),
+            efficiency(role.cp_per_strength),
+            efficiency(role.cp_per_magic),
+            role.atb_nodes,
+            role.accessory_nodes,
+            role.ability_nodes,
+            role.role_nodes
+        ));
+    }
+
+    //Cumulative values per stage below the summary.
+    for role in analysis.roles.iter() {
+        lines.push(String::new());
+        lines.push(format!("{:<10} {:>5} {:>8} {:>8} {:>6} {:>6} {:>6}", role.role, "STAGE", "CP", "SUM CP", "HP", "STR", "MAG"));
+        for stage in role.stages.iter() {
+            lines.push(format!(
+                "{:<10} {:>5} {:>8} {:>8} {:>6} {:>6} {:>6}",
+                "",
+                stage.stage,
+                stage.cp,
+                stage.cumulative_cp,
+                stage.cumulative_hp,
+                stage.cumulative_strength,
+                stage.cumulative_magic
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+pub fn plan_text(plan: &Plan) -> String {
+    let mut lines = vec![format!("{:<16} {:<10} {:>5} {:>6} {:>8}", "NAME", "ROLE", "STAGE", "COST", "SUM CP")];
+
+    for step in plan.steps.iter() {
+        lines.push(format!(
+            "{:<16} {:<10} {:>5} {:>6} {:>8}",
+            step.node_name, step.role, step.stage, step.cp_cost, step.cumulative_cp
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{} of {} CP, score {:.1}", plan.spent, plan.budget, plan.score));
+    lines.push(format!("HP +{} STR +{} MAG +{}", plan.hp, plan.strength, plan.magic));
+    if !plan.abilities.is_empty() {
+        lines.push(format!("Abilities: {}", plan.abilities.join(", ")));
+    }
+
+    lines.join("\n")
+}
+
+pub fn simulation_text(simulation: &Simulation) -> String {
+    let mut lines = vec![
+        simulation.character.clone(),
+        format!(
+            "{:<20} {:>7} {:>7} {:>7} {:>5} {:>6} {:>6} {:>6}  {:>3} {:>3} {:>3}",
+            "CHAPTER", "INCOME", "SPENT", "LEFT", "NODES", "HP", "STR", "MAG", "ATB", "ACC", "ROL"
+        ),
+    ];
+
+    let mut abilities = 0;
+    for chapter in simulation.chapters.iter() {
+        lines.push(format!(
+            "{:<20} {:>7} {:>7} {:>7} {:>5} {:>6} {:>6} {:>6}  {:>3} {:>3} {:>3}",
+            chapter.name,
+            chapter.income,
+            chapter.spent,
+            chapter.cp_left,
+            chapter.bought.len(),
+            chapter.hp,
+            chapter.strength,
+            chapter.magic,
+            chapter.atb_nodes,
+            chapter.accessory_nodes,
+            chapter.role_nodes
+        ));
+
+        //Only the abilities learned in this chapter.
+        if chapter.abilities.len() > abilities {
+            lines.push(format!("{:<20} + {}", "", chapter.abilities[abilities..].join(", ")));
+            abilities = chapter.abilities.len();
+        }
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{} nodes for {} CP left", simulation.remaining_nodes, simulation.remaining_cp));
+
+    lines.join("\n")
+}
+
+pub fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Serve { .. } => Err("serve has to be started by main".to_string()),
+        Command::Dump { file } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, crystarium) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+            write_output(&None, &dump(fstruct.layout(), &crystarium))
+        }
+        Command::Diff { old, new, json, patch } => {
+            let old_crystarium =
+                read_crystal_wdb_with_file(path_str(&old)?).map_err(|err| format!("{}: {}", old.display(), err))?;
+            let new_crystarium =
+                read_crystal_wdb_with_file(path_str(&new)?).map_err(|err| format!("{}: {}", new.display(), err))?;
+
+            let content = match (json, patch) {
+                (_, true) => {
+                    let patch = patch::from_diff(&old_crystarium, &new_crystarium).map_err(|err| err.to_string())?;
+                    patch::to_toml(&patch)
+                }
+                (true, _) => serde_json::to_string_pretty(&diff(&old_crystarium, &new_crystarium))
+                    .map_err(|err| err.to_string())?,
+                _ => diff(&old_crystarium, &new_crystarium).to_string(),
+            };
+
+            write_output(&None, &content)
+        }
+        Command::Apply { file, patch: patch_file, output } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, crystarium) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let text = fs::read_to_string(&patch_file).map_err(|err| format!("{}: {}", patch_file.display(), err))?;
+            let patched = patch::from_toml(&text)
+                .and_then(|patch| patch::apply(&patch, &crystarium))
+                .map_err(|err| format!("{}: {}", patch_file.display(), err))?;
+
+            let bytes = write_crystal_wdb(&fstruct, &patched).map_err(|err| err.to_string())?;
+            fs::write(&output, bytes).map_err(|err| format!("{}: {}", output.display(), err))
+        }
+        Command::Export { file, format, output } => {
+            let crystarium = read_crystal_wdb_with_file(path_str(&file)?)
+                .map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let content = match format {
+                ExportFormat::Json => export::to_json(&crystarium),
+                ExportFormat::Csv => export::to_csv(&crystarium, b','),
+                ExportFormat::Tsv => export::to_csv(&crystarium, b'\t'),
+            };
+
+            write_output(&output, &content)
+        }
+        Command::Import { file, input, format, output } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, original) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let text = fs::read_to_string(&input).map_err(|err| format!("{}: {}", input.display(), err))?;
+            let format = match format {
+                Some(format) => format,
+                None => ExportFormat::from_extension(&input)?,
+            };
+            let imported = match format {
+                ExportFormat::Json => export::from_json(&text).and_then(|imported| export::import(&original, imported)),
+                ExportFormat::Csv => export::from_csv(&original, &text, b','),
+                ExportFormat::Tsv => export::from_csv(&original, &text, b'\t'),
+            }
+            .map_err(|err| format!("{}: {}", input.display(), err))?;
+
+            let bytes = write_crystal_wdb(&fstruct, &imported).map_err(|err| err.to_string())?;
+            fs::write(&output, bytes).map_err(|err| format!("{}: {}", output.display(), err))
+        }
+        Command::Edit { file, node, cp_cost, value, node_type, stage, role, ability, output } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, mut crystarium) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let target = crystarium
+                .nodes
+                .iter_mut()
+                .find(|n| n.node_name == node)
+                .ok_or_else(|| format!("node {} not found in {}", node, file.display()))?;
+
+            if let Some(cp_cost) = cp_cost {
+                target.cp_cost = cp_cost;
+            }
+            if let Some(value) = value {
+                target.node_value = value;
+            }
+            if let Some(node_type) = node_type {
+                target.node_type = node_type;
+            }
+            if let Some(stage) = stage {
+                target.stage = stage;
+            }
+            if let Some(role) = role {
+                target.role = role as u8;
+            }
+            if let Some(ability) = ability {
+                target.ability = ability;
+            }
+
+            let bytes = write_crystal_wdb(&fstruct, &crystarium).map_err(|err| err.to_string())?;
+            fs::write(&output, bytes).map_err(|err| format!("{}: {}", output.display(), err))
+        }
+        Command::Analyze { file, json } => {
+            let crystarium = read_crystal_wdb_with_file(path_str(&file)?)
+                .map_err(|err| format!("{}: {}", file.display(), err))?;
+            let analysis = analyze(&crystarium);
+
+            let content = match json {
+                true => serde_json::to_string_pretty(&analysis).map_err(|err| err.to_string())?,
+                false => analysis_text(&analysis),
+            };
+
+            write_output(&None, &content)
+        }
+        Command::Optimize { file, budget, max_stage, weights, json } => {
+            let crystarium = read_crystal_wdb_with_file(path_str(&file)?)
+                .map_err(|err| format!("{}: {}", file.display(), err))?;
+            let plan = optimize(&crystarium, budget, &weights.into(), max_stage);
+
+            let content = match json {
+                true => serde_json::to_string_pretty(&plan).map_err(|err| err.to_string())?,
+                false => plan_text(&plan),
+            };
+
+            write_output(&None, &content)
+        }
+        Command::Simulate { file, income, policy, roles, weights, json } => {
+            let crystarium = read_crystal_wdb_with_file(path_str(&file)?)
+                .map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let text = fs::read_to_string(&income).map_err(|err| format!("{}: {}", income.display(), err))?;
+            let income_table = match income.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => Income::from_json(&text),
+                _ => Income::from_toml(&text),
+            }
+            .map_err(|err| format!("{}: {}", income.display(), err))?;
+
+            let policy = match policy {
+                PolicyArg::Cheapest => Policy::Cheapest,
+                PolicyArg::Roles if roles.is_empty() => return Err("the roles policy needs --roles".to_string()),
+                PolicyArg::Roles => Policy::Roles(roles.into_iter().map(|role| role as u8).collect()),
+                PolicyArg::Weighted => Policy::Weighted(weights.into()),
+            };
+            let simulation = simulate(&crystarium, &income_table, &policy);
+
+            let content = match json {
+                true => serde_json::to_string_pretty(&simulation).map_err(|err| err.to_string())?,
+                false => simulation_text(&simulation),
+            };
+
+            write_output(&None, &content)
+        }
+        Command::Rebalance { file, cost, value, cap, stage, role, node_type, output } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, crystarium) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let transform = Transform {
+                filter: Filter {
+                    stages: stage,
+                    roles: role.into_iter().map(|role| role as u8).collect(),
+                    types: node_type,
+                },
+                cost_factor: cost,
+                value_factor: value,
+                value_cap: cap,
+            };
+            let rebalanced = rebalance(&crystarium, &[transform])?;
+
+            //Without output the changes are only shown, like the preview of the web form.
+            let changes = diff(&crystarium, &rebalanced);
+            match output {
+                Some(output) => {
+                    let bytes = write_crystal_wdb(&fstruct, &rebalanced).map_err(|err| err.to_string())?;
+                    fs::write(&output, bytes).map_err(|err| format!("{}: {}", output.display(), err))?;
+                    write_output(&None, &format!("{} nodes changed", changes.changed.len()))
+                }
+                None => write_output(&None, &changes.to_string()),
+            }
+        }
+        Command::Randomize {
+            file,
+            seed,
+            scope,
+            keep_role_nodes,
+            keep_stage_budget,
+            keep_atb_order,
+            output,
+            spoiler,
+        } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let (fstruct, crystarium) =
+                read_crystal_wdb_with_structure(data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            //A clock seed is still written to the spoiler log, so the race can be reproduced.
+            let seed = match seed {
+                Some(seed) => seed,
+                None => SystemTime::now().duration_since(UNIX_EPOCH).map_err(|err| err.to_string())?.as_nanos() as u64,
+            };
+            let options = Options {
+                seed,
+                scope: match scope {
+                    ScopeArg::Role => Scope::Role,
+                    ScopeArg::Stage => Scope::Stage,
+                },
+                keep_role_nodes,
+                keep_stage_budget,
+                keep_atb_order,
+            };
+            let (randomized, spoiler_log) = randomize(&crystarium, &options);
+
+            let bytes = write_crystal_wdb(&fstruct, &randomized).map_err(|err| err.to_string())?;
+            fs::write(&output, bytes).map_err(|err| format!("{}: {}", output.display(), err))?;
+
+            let json = spoiler.as_ref().and_then(|path| path.extension()).is_some_and(|extension| extension == "json");
+            let content = match json {
+                true => serde_json::to_string_pretty(&spoiler_log).map_err(|err| err.to_string())?,
+                false => spoiler_log.to_string(),
+            };
+            write_output(&spoiler, &content)
+        }
+        Command::Table { file, format, output } => {
+            let data = fs::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+            let table = read_table(&data).map_err(|err| format!("{}: {}", file.display(), err))?;
+
+            let content = match format {
+                ExportFormat::Json => serde_json::to_string_pretty(&table).map_err(|err| err.to_string())?,
+                ExportFormat::Csv => export::table_to_csv(&table, b','),
+                ExportFormat::Tsv => export::table_to_csv(&table, b'\t'),
+            };
+
+            write_output(&output, &content)
+        }
+        Command::Validate { files, json } => {
+            let mut reports = Vec::new();
+
+            for file in files.iter() {
+                //A file is fine when it can be read, the writer gives back the same bytes and no node is suspicious.
+                let mut report = FileReport { file: file.display().to_string(), ..Default::default() };
+                let parsed = fs::read(file).map_err(|err| err.to_string()).and_then(|data| {
+                    let (fstruct, crystarium) =
+                        read_crystal_wdb_with_structure(data.clone()).map_err(|err| err.to_string())?;
+                    Ok((data, fstruct, crystarium))
+                });
+
+                match parsed {
+                    Ok((data, fstruct, crystarium)) => {
+                        report.nodes = crystarium.nodes.len();
+                        report.issues = validation::validate(&fstruct, &crystarium);
+                        report.error = match write_crystal_wdb(&fstruct, &crystarium) {
+                            Ok(written) if written == data => None,
+                            Ok(_) => Some("written file differs from the original".to_string()),
+                            Err(err) => Some(err.to_string()),
+                        };
+                    }
+                    Err(err) => report.error = Some(err),
+                }
+
+                reports.push(report);
+            }
+
+            let output = match json {
+                true => serde_json::to_string_pretty(&reports).map_err(|err| err.to_string())?,
+                false => reports.iter().map(FileReport::to_string).collect::<Vec<_>>().join("\n"),
+            };
+            write_output(&None, &output)?;
+
+            let failed = reports.iter().filter(|report| !report.is_ok()).count();
+            match failed {
+                0 => Ok(()),
+                _ => Err(format!("{} of {} files failed validation", failed, files.len())),
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use super::{Entry, FileStructure, Node, NodeType, WdbError};
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::BTreeMap;
+
+// Record layouts of the crystarium files. FF13 records are the core
+//     • int: CP cost
+//     • int: String offset - Ability Id
+//     • short: Node value
+//     • byte: node type
+//     • byte: stage * 16 + role
+// other layouts are read with the same core and only differ in the bytes behind it.
+pub const CORESIZE: usize = 12;
+
+pub trait Layout: Sync {
+    fn name(&self) -> &'static str;
+
+    //Whether the file belongs to this layout, from the node names or the records.
+    fn detect(&self, fstruct: &FileStructure) -> bool;
+
+    //Character a node belongs to, from the node name.
+    fn character(&self, node_name: &str) -> Option<&'static str>;
+
+    fn decode(&self, fstruct: &FileStructure, name: &str, record: &[u8]) -> Result<Node, WdbError>;
+
+    fn encode(&self, node: &Node, ability_offset: i32, original: Option<&[u8]>) -> Result<Vec<u8>, WdbError>;
+
+    //String offset of the ability inside a record, used to keep the offsets of unchanged nodes.
+    fn ability_offset(&self, record: &[u8]) -> Option<i32> {
+        record.get(4..8).map(BigEndian::read_i32)
+    }
+}
+
+pub struct Ff13;
+pub struct Raw;
+
+//Checked in this order, files nothing matches are read as FF13.
+pub static LAYOUTS: [&dyn Layout; 2] = [&Ff13, &Raw];
+
+pub fn detect(fstruct: &FileStructure) -> &'static dyn Layout {
+    LAYOUTS.iter().copied().find(|layout| layout.detect(fstruct)).unwrap_or(&Ff13)
+}
+
+pub fn find(name: &str) -> Option<&'static dyn Layout> {
+    LAYOUTS.iter().copied().find(|layout| layout.name() == name)
+}
+
+fn node_names(fstruct: &FileStructure) -> impl Iterator<Item = &str> {
+    fstruct.entries().iter().map(Entry::name).filter(|name| !name.starts_with('!'))
+}
+
+fn code(node_name: &str) -> Option<&str> {
+    node_name.strip_prefix("cr_")?.get(..2)
+}
+
+fn decode_core(fstruct: &FileStructure, name: &str, record: &[u8]) -> Result<Node, WdbError> {
+    let record = record.get(..CORESIZE).ok_or_else(|| WdbError::EntryOutOfRange(name.to_string()))?;
+
+    //Resolve the ability id from the !!string section, nodes without ability point to an empty string.
+    let ability = fstruct.ability_at(BigEndian::read_i32(&record[4..8]));
+
+    Ok(Node {
+        node_name: name.to_string(),
+        cp_cost: BigEndian::read_i32(&record[0..4]),
+        ability: ability.to_string(),
+        node_value: BigEndian::read_i16(&record[8..10]),
+        node_type: NodeType::from(record[10]),
+        stage: record[11] / 16,
+        role: record[11] % 16,
+        type_raw: record[10],
+        stage_role_raw: record[11],
+        ..Default::default()
+    })
+}
+
+fn encode_core(node: &Node, ability_offset: i32) -> Result<Vec<u8>, WdbError> {
+    if node.stage > 15 {
+        return Err(WdbError::FieldOutOfRange { name: node.node_name.clone(), field: "stage" });
+    }
+    if node.role > 15 {
+        return Err(WdbError::FieldOutOfRange { name: node.node_name.clone(), field: "role" });
+    }
+
+    let mut record = vec![0; CORESIZE];
+    BigEndian::write_i32(&mut record[0..4], node.cp_cost);
+    BigEndian::write_i32(&mut record[4..8], ability_offset);
+    BigEndian::write_i16(&mut record[8..10], node.node_value);
+    record[10] = node.type_byte();
+    record[11] = node.stage_role_byte();
+
+    Ok(record)
+}
+
+//Every byte behind the core is shown and edited as byte_<position>, so byte_12 is the first one.
+fn decode_extra(record: &[u8]) -> BTreeMap<String, i64> {
+    record
+        .iter()
+        .enumerate()
+        .skip(CORESIZE)
+        .map(|(position, byte)| (format!("byte_{}", position), i64::from(*byte)))
+        .collect()
+}
+
+fn encode_extra(node: &Node, record: &mut Vec<u8>, original: Option<&[u8]>) -> Result<(), WdbError> {
+    let length = original.map_or(CORESIZE, <[u8]>::len).max(CORESIZE + node.extra.len());
+
+    for position in CORESIZE..length {
+        let byte = match node.extra.get(&format!("byte_{}", position)) {
+            Some(value) => u8::try_from(*value)
+                .map_err(|_| WdbError::FieldOutOfRange { name: node.node_name.clone(), field: "extra byte" })?,
+            None => original.and_then(|o| o.get(position).copied()).unwrap_or_default(),
+        };
+        record.push(byte);
+    }
+
+    Ok(())
+}
+
+impl Layout for Ff13 {
+    fn name(&self) -> &'static str {
+        "FF13"
+    }
+
+    fn detect(&self, fstruct: &FileStructure) -> bool {
+        node_names(fstruct).any(|name| self.character(name).is_some())
+    }
+
+    fn character(&self, node_name: &str) -> Option<&'static str> {
+        match code(node_name)? {
+            "fa" => Some("Fang"),
+            "hp" => Some("Hope"),
+            "lt" => Some("Lightning"),
+            "sz" => Some("Sazh"),
+            "sn" => Some("Snow"),
+            "va" => Some("Vanille"),
+            _ => None,
+        }
+    }
+
+    fn decode(&self, fstruct: &FileStructure, name: &str, record: &[u8]) -> Result<Node, WdbError> {
+        decode_core(fstruct, name, record)
+    }
+
+    fn encode(&self, node: &Node, ability_offset: i32, original: Option<&[u8]>) -> Result<Vec<u8>, WdbError> {
+        let mut record = encode_core(node, ability_offset)?;
+
+        //Bytes behind the known fields are passed through untouched.
+        if let Some(tail) = original.and_then(|o| o.get(CORESIZE..)) {
+            record.extend_from_slice(tail);
+        }
+
+        Ok(record)
+    }
+}
+
+//Placeholder for crystal files which aren't FF13, e.g. FF13-2. No such file has been checked against it, so
+//neither the node names nor the bytes behind the core are known. The core is decoded like FF13 and everything
+//behind it is kept as raw bytes.
+impl Layout for Raw {
+    fn name(&self) -> &'static str {
+        "Raw"
+    }
+
+    //FF13 records end with the core, longer records in files without FF13 node names can't be decoded as FF13.
+    fn detect(&self, fstruct: &FileStructure) -> bool {
+        let mut records = fstruct.entries().iter().filter(|entry| !entry.name().starts_with('!'));
+        !Ff13.detect(fstruct) && records.any(|entry| entry.data().len() > CORESIZE)
+    }
+
+    fn character(&self, node_name: &str) -> Option<&'static str> {
+        node_name.starts_with("cr_").then_some("Unknown")
+    }
+
+    fn decode(&self, fstruct: &FileStructure, name: &str, record: &[u8]) -> Result<Node, WdbError> {
+        Ok(Node { extra: decode_extra(record), ..decode_core(fstruct, name, record)? })
+    }
+
+    fn encode(&self, node: &Node, ability_offset: i32, original: Option<&[u8]>) -> Result<Vec<u8>, WdbError> {
+        let mut record = encode_core(node, ability_offset)?;
+        encode_extra(node, &mut record, original)?;
+
+        Ok(record)
+    }
+}
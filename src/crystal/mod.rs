@@ -1,5 +1,12 @@
 use byteorder::{BigEndian, ByteOrder};
-use std::{ fmt, fs, io::{self, BufRead, BufReader, Cursor, Read, Seek}, vec };
+use serde::{Deserialize, Serialize};
+use std::{ collections::BTreeMap, error, fmt, fs, io::{self, BufRead, BufReader, Cursor, Read, Seek, Write}, str::FromStr };
+
+pub mod layout;
+#[cfg(test)]
+mod tests;
+
+pub use layout::Layout;
 
 // WDB
 //     • int: CP cost
@@ -16,25 +23,59 @@ use std::{ fmt, fs, io::{self, BufRead, BufReader, Cursor, Read, Seek}, vec };
 //     • byte / 16: Stage
 //     • byte % 16: Role
 // 00 00 2E E0, 00 00 01 D9, 00 C3, 01, (91)->0101 1011
-#[derive(Debug, Default)]
+#[derive(Debug)]
+pub enum WdbError {
+    BadMagic(String),
+    TruncatedHeader,
+    MissingSection(&'static str),
+    OffsetMismatch {
+        section: &'static str,
+        expected: i32,
+        position: u64,
+    },
+    InvalidUtf8,
+    EntryOutOfRange(String),
+    NameTooLong(String),
+    FieldOutOfRange {
+        name: String,
+        field: &'static str,
+    },
+    UnknownFieldType {
+        section: &'static str,
+        code: u32,
+    },
+    Io(io::Error),
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct Entry {
     name: String,
     offset: i32,
     length: i32,
+    //Bytes behind the null terminator of the name and behind offset/length, kept for writing back.
+    name_padding: Vec<u8>,
+    padding: Vec<u8>,
+    //Bytes between the end of the previous entry's data and this entry's offset.
+    gap: Vec<u8>,
+    //Raw data of the entry, known sections are decoded into the file structure as well.
+    data: Vec<u8>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct FileStructure {
     magic: String,
     count: i32,
+    header_padding: Vec<u8>,
     entries: Vec<Entry>,
     stringlist: Vec<String>,
     stringtypelist: Vec<u8>,
     typelist: Vec<u8>,
     version: i32,
+    //Bytes behind the last entry's data.
+    trailing: Vec<u8>,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeType {
     #[default]
     HP,
@@ -59,7 +100,7 @@ pub enum NodeRole {
     INVALID,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub node_name: String,
     pub cp_cost: i32,
@@ -68,86 +109,224 @@ pub struct Node {
     pub node_type: NodeType,
     pub stage: u8,
     pub role: u8,
+    //Bytes as read from the file, the decoded fields above lose unknown values.
+    #[serde(default)]
+    pub type_raw: u8,
+    #[serde(default)]
+    pub stage_role_raw: u8,
+    //Bytes behind the FF13 record of files read with the raw layout, see layout.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, i64>,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Crystarium {
     pub character: String,
     pub nodes: Vec<Node>,
 }
 
-pub trait ReadUtilities {
-    fn load_part<T: BufRead + Seek>(reader: &mut T, size: usize) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size);
+impl fmt::Display for WdbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WdbError::BadMagic(magic) => write!(f, "bad magic {:?}, expected \"WPD\"", magic),
+            WdbError::TruncatedHeader => write!(f, "file ends inside the header"),
+            WdbError::MissingSection(section) => write!(f, "section {} not found", section),
+            WdbError::OffsetMismatch { section, expected, position } => write!(
+                f,
+                "section {} expected at offset {}, but reader is at {}",
+                section, expected, position
+            ),
+            WdbError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            WdbError::EntryOutOfRange(name) => {
+                write!(f, "entry {} lies outside of the file", name.trim_end_matches('\0'))
+            }
+            WdbError::NameTooLong(name) => write!(f, "entry name {} is longer than 15 bytes", name.trim_end_matches('\0')),
+            WdbError::FieldOutOfRange { name, field } => {
+                write!(f, "{} of node {} does not fit into the record", field, name.trim_end_matches('\0'))
+            }
+            WdbError::UnknownFieldType { section, code } => write!(f, "{} holds unknown field type {}", section, code),
+            WdbError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl error::Error for WdbError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            WdbError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WdbError {
+    fn from(err: io::Error) -> Self {
+        WdbError::Io(err)
+    }
+}
+
+pub(crate) trait ReadUtilities {
+    fn load_part<T: BufRead + Seek>(reader: &mut T, size: usize) -> Result<Vec<u8>, WdbError> {
+        let mut buf = Vec::new();
 
         // Get a reader for the next `size` amount of bytes
         let mut part_reader = reader.take(size as u64);
 
-        // Read the part into the buffer
-        part_reader.read_to_end(&mut buf).unwrap();
+        // Read the part into the buffer, a short read means the file is cut off.
+        part_reader.read_to_end(&mut buf)?;
+        if buf.len() < size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
 
         // Return the buffer
-        buf
+        Ok(buf)
     }
 
-    fn load_string<T: BufRead + Seek>(reader: &mut T, size: usize) -> String {
+    fn load_string<T: BufRead + Seek>(reader: &mut T, size: usize) -> Result<String, WdbError> {
         //Load a specific size of bytes into String::from_utf8 to extract the string from the bytes
-        String::from_utf8(Self::load_part(reader, size))
-            .unwrap()
-            .to_string()
+        String::from_utf8(Self::load_part(reader, size)?).map_err(|_| WdbError::InvalidUtf8)
     }
 
-    fn load_string_eof<T: BufRead + Seek>(reader: &mut T) -> String {
+    fn load_string_eof<T: BufRead + Seek>(reader: &mut T) -> Result<String, WdbError> {
         //Read until null terminator comes.
         let mut buf_vec: Vec<u8> = Default::default();
-        let _ = reader.read_until(b'\0', &mut buf_vec).unwrap();
+        reader.read_until(b'\0', &mut buf_vec)?;
+
+        //Without terminator the string runs into the end of the file.
+        if buf_vec.last() != Some(&b'\0') {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        String::from_utf8(buf_vec).map_err(|_| WdbError::InvalidUtf8)
+    }
+}
+
+pub(crate) trait WriteUtilities {
+    fn write_i32<W: Write>(writer: &mut W, value: i32) -> Result<(), WdbError> {
+        let mut buf = [0; 4];
+        BigEndian::write_i32(&mut buf, value);
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
 
-        String::from_utf8(buf_vec).expect("Byte to string convert failed")
+    fn write_string_padded<W: Write>(writer: &mut W, value: &str, size: usize) -> Result<(), WdbError> {
+        //Write the string with its null terminator and fill the rest of the field with zeros.
+        let mut buf = value.trim_end_matches('\0').as_bytes().to_vec();
+        buf.push(0);
+        if buf.len() > size {
+            return Err(WdbError::NameTooLong(value.to_string()));
+        }
+        buf.resize(size, 0);
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+}
+
+//Header reads running into the end of the file are reported as truncated header.
+fn header_err(err: WdbError) -> WdbError {
+    match err {
+        WdbError::Io(io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof => WdbError::TruncatedHeader,
+        err => err,
     }
 }
 
 impl ReadUtilities for Entry {}
+impl WriteUtilities for Entry {}
 impl Entry {
+    pub(crate) const HEADERSIZE: usize = 16;
+    pub(crate) const ENTRYSIZE: usize = 32;
+    pub(crate) const STRINGSIZE: usize = 16;
+
+    pub(crate) fn write_entry<W: Write>(
+        writer: &mut W,
+        name: &str,
+        original: Option<&Entry>,
+        offset: i32,
+        length: i32,
+    ) -> Result<(), WdbError> {
+        //Reuse the original padding bytes, new entries are padded with zeros.
+        match original.filter(|e| {
+            e.name.trim_end_matches('\0') == name.trim_end_matches('\0')
+                && e.name.len() + e.name_padding.len() == Self::STRINGSIZE
+        }) {
+            Some(entry) => {
+                writer.write_all(entry.name.as_bytes())?;
+                writer.write_all(&entry.name_padding)?;
+            }
+            None => Self::write_string_padded(writer, name, Self::STRINGSIZE)?,
+        }
+
+        Self::write_i32(writer, offset)?;
+        Self::write_i32(writer, length)?;
+
+        match original.map(|e| &e.padding).filter(|p| p.len() == Self::ENTRYSIZE - Self::STRINGSIZE - 8) {
+            Some(padding) => writer.write_all(padding)?,
+            None => writer.write_all(&[0; 8])?,
+        }
+
+        Ok(())
+    }
+
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn load_entries<T: BufRead + Seek>(reader: &mut T, count: i32) -> Vec<Entry> {
-        const ENTRYSIZE: usize = 32;
-        const STRINGSIZE: usize = 16;
+    /// Name without the null terminator, e.g. `!!string` or `cr_lt0001000000`.
+    pub fn name(&self) -> &str {
+        self.name.trim_end_matches('\0')
+    }
 
-        let mut entries: Vec<Entry> = Vec::new();
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
 
-        //Skip 8 bytes
-        Self::load_part(reader, 8);
+    pub fn length(&self) -> i32 {
+        self.length
+    }
+
+    /// Raw record of a node entry, or the data of a `!!` section as read from the file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub(crate) fn load_entries<T: BufRead + Seek>(reader: &mut T, count: i32) -> Result<Vec<Entry>, WdbError> {
+        let mut entries: Vec<Entry> = Vec::new();
 
         for _ in 0..count {
-            let name = Self::load_string_eof(reader);
-            let mut padding_size = STRINGSIZE - name.len();
-            let mut padding: Vec<u8>;
-            if name.starts_with('!') {
-                padding = vec![0; padding_size];
-                reader.read(&mut padding).expect("Skip Padding failed");
+            let name = Self::load_string_eof(reader).map_err(header_err)?;
+
+            //Names are stored in a 16 byte field, anything longer would run into offset and length.
+            if name.len() > Self::STRINGSIZE {
+                return Err(WdbError::EntryOutOfRange(name));
             }
 
-            let offset = BigEndian::read_i32(&Self::load_part(reader, 4));
-            let length = BigEndian::read_i32(&Self::load_part(reader, 4));
+            let mut padding_size = Self::STRINGSIZE - name.len();
+            let name_padding = Self::load_part(reader, padding_size).map_err(header_err)?;
 
-            {
-                // Jeder Entry ist 32 Stellen lang, also sicherstellen, das genug padding geskippt wird.
-                padding_size = ENTRYSIZE - STRINGSIZE - 4 - 4;
-                padding = vec![0; padding_size];
-                reader.read(&mut padding).expect("Skip Padding failed");
+            let offset = BigEndian::read_i32(&Self::load_part(reader, 4).map_err(header_err)?);
+            let length = BigEndian::read_i32(&Self::load_part(reader, 4).map_err(header_err)?);
+
+            // Jeder Entry ist 32 Stellen lang, also sicherstellen, das genug padding gelesen wird.
+            padding_size = Self::ENTRYSIZE - Self::STRINGSIZE - 4 - 4;
+            let padding = Self::load_part(reader, padding_size).map_err(header_err)?;
+
+            if offset < 0 || length < 0 {
+                return Err(WdbError::EntryOutOfRange(name));
             }
 
             entries.push(Entry {
-                name: name,
-                offset: offset,
-                length: length,
+                name,
+                offset,
+                length,
+                name_padding,
+                padding,
+                ..Default::default()
             })
         }
 
-        entries
+        Ok(entries)
     }
 }
 
@@ -155,72 +334,238 @@ impl Entry {
 impl ReadUtilities for FileStructure {}
 
 impl FileStructure {
-    pub fn load_version<T: BufRead + Seek>(reader: &mut T, entries: &Vec<Entry>) -> i32 {
-        let _ = entries
+    //Sections decoded into the file structure, any other `!!` section is copied through as read.
+    const SECTIONS: [&'static str; 4] = ["!!string", "!!strtypelist", "!!typelist", "!!version"];
+
+    fn find_section<'a>(entries: &'a [Entry], name: &'static str) -> Result<&'a Entry, WdbError> {
+        entries
             .iter()
-            .find(|entry| entry.name == "!!version\0")
-            .expect("version not found");
+            .find(|entry| entry.name.trim_end_matches('\0') == name)
+            .ok_or(WdbError::MissingSection(name))
+    }
 
-        let version = BigEndian::read_i32(&Self::load_part(reader, 4));
-        version
+    //Read the bytes in front of the entry's data, the reader must not be past its offset already.
+    fn load_gap<T: BufRead + Seek>(reader: &mut T, entry: &mut Entry, section: &'static str) -> Result<(), WdbError> {
+        let position = reader.stream_position()?;
+
+        if position > entry.offset as u64 {
+            return Err(WdbError::OffsetMismatch {
+                section,
+                expected: entry.offset,
+                position,
+            });
+        }
+
+        entry.gap = Self::load_part(reader, (entry.offset as u64 - position) as usize)?;
+
+        Ok(())
     }
 
-    pub fn load_typelist<T: BufRead + Seek>(reader: &mut T, entries: &Vec<Entry>) -> Vec<u8> {
-        let entry = entries
-            .iter()
-            .find(|entry| entry.name == "!!typelist\0")
-            .expect("typelist not found");
-        let mut typelist = vec![0; entry.length as usize];
+    pub(crate) fn load_version(entries: &[Entry]) -> Result<i32, WdbError> {
+        let entry = Self::find_section(entries, "!!version")?;
+        let data = entry.data.get(..4).ok_or_else(|| WdbError::EntryOutOfRange(entry.name.clone()))?;
 
-        reader.read_exact(&mut typelist).unwrap();
+        Ok(BigEndian::read_i32(data))
+    }
 
-        typelist
+    pub(crate) fn load_typelist(entries: &[Entry]) -> Result<Vec<u8>, WdbError> {
+        Ok(Self::find_section(entries, "!!typelist")?.data.clone())
     }
 
-    pub fn load_stringtypelist<T: BufRead + Seek>(reader: &mut T, entries: &Vec<Entry>) -> Vec<u8> {
-        let entry = entries
-            .iter()
-            .find(|entry| entry.name == "!!strtypelist\0")
-            .expect("strtypelist not found");
-        let mut stringtypelist = vec![0; entry.length as usize];
+    pub(crate) fn load_stringtypelist(entries: &[Entry]) -> Result<Vec<u8>, WdbError> {
+        Ok(Self::find_section(entries, "!!strtypelist")?.data.clone())
+    }
 
-        reader.read_exact(&mut stringtypelist).unwrap();
+    pub(crate) fn load_stringlist(entries: &[Entry]) -> Result<Vec<String>, WdbError> {
+        let entry = Self::find_section(entries, "!!string")?;
 
-        stringtypelist
+        //Every string keeps its null terminator, so the list concatenates back to the section.
+        entry
+            .data
+            .split_inclusive(|byte| *byte == 0)
+            .map(|string| String::from_utf8(string.to_vec()).map_err(|_| WdbError::InvalidUtf8))
+            .collect()
     }
 
-    pub fn load_stringlist<T: BufRead + Seek>(reader: &mut T, entries: &Vec<Entry>) -> Vec<String> {
-        let entry = entries
-            .iter()
-            .find(|entry| entry.name == "!!string\0")
-            .expect("string not found");
-        let mut stringlist: Vec<String> = Default::default();
-        let mut position = reader.stream_position().unwrap() as i32;
-
-        if position == entry.offset {
-            while position < entry.offset + entry.length {
-                let name = Self::load_string_eof(reader);
-                stringlist.push(name.clone());
-                position += name.len() as i32;
+    pub(crate) fn load_data<T: BufRead + Seek>(reader: &mut T, entries: &mut [Entry]) -> Result<(), WdbError> {
+        //Sections and node records are read in the order of their offsets, the table doesn't have to be sorted.
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        order.sort_by_key(|index| entries[*index].offset);
+
+        for index in order {
+            let entry = &mut entries[index];
+
+            //Running into the end of the file means a node entry points outside of it.
+            let name = entry.name.clone();
+            let is_section = name.starts_with('!');
+            let out_of_range = |err| match err {
+                WdbError::Io(_) if !is_section => WdbError::EntryOutOfRange(name.clone()),
+                err => err,
+            };
+
+            let known = Self::SECTIONS.iter().copied().find(|section| *section == entry.name());
+            let section = known.unwrap_or(if is_section { "unknown section" } else { "node record" });
+            Self::load_gap(reader, entry, section).map_err(out_of_range)?;
+            entry.data = Self::load_part(reader, entry.length as usize).map_err(out_of_range)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load<T: BufRead + Seek>(reader: &mut T) -> Result<FileStructure, WdbError> {
+        let mut fstruct = FileStructure {
+            magic: Self::load_string(reader, 4).map_err(header_err)?,
+            ..Default::default()
+        };
+
+        if fstruct.magic.trim_end_matches('\0') != "WPD" {
+            return Err(WdbError::BadMagic(fstruct.magic));
+        }
+
+        fstruct.count = BigEndian::read_i32(&Self::load_part(reader, 4).map_err(header_err)?);
+        fstruct.header_padding = Self::load_part(reader, 8).map_err(header_err)?;
+        fstruct.entries = Entry::load_entries(reader, fstruct.count)?;
+        Self::load_data(reader, &mut fstruct.entries)?;
+        fstruct.stringlist = Self::load_stringlist(&fstruct.entries)?;
+        fstruct.stringtypelist = Self::load_stringtypelist(&fstruct.entries)?;
+        fstruct.typelist = Self::load_typelist(&fstruct.entries)?;
+        fstruct.version = Self::load_version(&fstruct.entries)?;
+        reader.read_to_end(&mut fstruct.trailing)?;
+
+        Ok(fstruct)
+    }
+
+    //Record layout of the game the file belongs to, chosen by the node names and the record length.
+    pub fn layout(&self) -> &'static dyn Layout {
+        layout::detect(self)
+    }
+
+    pub fn magic(&self) -> &str {
+        &self.magic
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Strings of the `!!string` section, each with its null terminator.
+    pub fn strings(&self) -> &[String] {
+        &self.stringlist
+    }
+
+    pub fn string_types(&self) -> &[u8] {
+        &self.stringtypelist
+    }
+
+    pub fn types(&self) -> &[u8] {
+        &self.typelist
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Resolves an offset into the `!!string` section, `None` if it lies outside of it.
+    /// Offsets may point into the middle of a string, the ability is the rest up to the null terminator.
+    pub fn string_at(&self, offset: i32) -> Option<&str> {
+        //Strings are stored back to back, so walk the list and sum up the lengths (null terminator included).
+        let mut position = 0;
+
+        for string in self.stringlist.iter() {
+            if (position..position + string.len() as i32).contains(&offset) {
+                return string.get((offset - position) as usize..).map(|rest| rest.trim_end_matches('\0'));
+            }
+            position += string.len() as i32;
+        }
+
+        None
+    }
+
+    //Ability a record offset decodes to, offsets which don't resolve give an empty ability.
+    pub(crate) fn ability_at(&self, offset: i32) -> &str {
+        self.string_at(offset).unwrap_or_default()
+    }
+}
+
+//Empty implementation for including functions.
+impl WriteUtilities for FileStructure {}
+
+impl FileStructure {
+    fn ability_offset(&self, stringlist: &mut Vec<String>, node: &Node, original: Option<&Entry>) -> i32 {
+        //Keep the original offset as long as it still decodes to the node's ability, unresolved ones included.
+        if let Some(offset) = original.and_then(|e| self.layout().ability_offset(&e.data)) {
+            if self.ability_at(offset) == node.ability {
+                return offset;
+            }
+        }
+
+        //Abilities which are not yet part of the string section are appended to it.
+        let mut position = 0;
+        for string in stringlist.iter() {
+            if string.trim_end_matches('\0') == node.ability {
+                return position;
             }
-        } else {
-            panic!("position/offset mismatch");
+            position += string.len() as i32;
         }
+        stringlist.push(format!("{}\0", node.ability));
 
-        stringlist
+        position
     }
 
-    pub fn load<T: BufRead + Seek>(reader: &mut T) -> FileStructure {
-        let mut fstruct = FileStructure::default();
-        fstruct.magic = Self::load_string(reader, 4);
-        fstruct.count = BigEndian::read_i32(&Self::load_part(reader, 4));
-        fstruct.entries = Entry::load_entries(reader, fstruct.count);
-        fstruct.stringlist = Self::load_stringlist(reader, &mut fstruct.entries);
-        fstruct.stringtypelist = Self::load_stringtypelist(reader, &mut fstruct.entries);
-        fstruct.typelist = Self::load_typelist(reader, &mut fstruct.entries);
-        fstruct.version = Self::load_version(reader, &mut fstruct.entries);
+    pub fn write<W: Write>(&self, writer: &mut W, crystarium: &Crystarium) -> Result<(), WdbError> {
+        let mut stringlist = self.stringlist.clone();
+        let layout = self.layout();
+
+        //Encode the node records first, new abilities extend the string section.
+        let mut records: Vec<(&str, Option<&Entry>, Vec<u8>)> = Vec::with_capacity(crystarium.nodes.len());
+        for node in crystarium.nodes.iter() {
+            let original = self
+                .entries
+                .iter()
+                .find(|e: &&Entry| !e.name.starts_with('!') && e.name.trim_end_matches('\0') == node.node_name);
+            let ability_offset = self.ability_offset(&mut stringlist, node, original);
+            let record = layout.encode(node, ability_offset, original.map(|e| e.data.as_slice()))?;
+            records.push((&node.node_name, original, record));
+        }
+
+        //Section contents in the order of the entry table, followed by the node records.
+        //Only the string section can change, unknown sections are copied through like the gaps.
+        let mut output: Vec<(&str, Option<&Entry>, Vec<u8>)> = Vec::new();
+        for entry in self.entries.iter().filter(|e: &&Entry| e.name.starts_with('!')) {
+            let data = match entry.name.trim_end_matches('\0') {
+                "!!string" => stringlist.concat().into_bytes(),
+                _ => entry.data.clone(),
+            };
+            output.push((&entry.name, Some(entry), data));
+        }
+        output.append(&mut records);
+
+        //Write header
+        Self::write_string_padded(writer, &self.magic, 4)?;
+        Self::write_i32(writer, output.len() as i32)?;
+        match self.header_padding.len() {
+            8 => writer.write_all(&self.header_padding)?,
+            _ => writer.write_all(&[0; 8])?,
+        }
+
+        //Write entry table
+        let mut offset = Entry::HEADERSIZE + Entry::ENTRYSIZE * output.len();
+        for (name, original, data) in output.iter() {
+            offset += original.map_or(0, |e| e.gap.len());
+            Entry::write_entry(writer, name, *original, offset as i32, data.len() as i32)?;
+            offset += data.len();
+        }
+
+        //Write entry data
+        for (_, original, data) in output.iter() {
+            if let Some(entry) = original {
+                writer.write_all(&entry.gap)?;
+            }
+            writer.write_all(data)?;
+        }
+        writer.write_all(&self.trailing)?;
 
-        fstruct
+        Ok(())
     }
 }
 
@@ -240,6 +585,36 @@ impl fmt::Display for NodeType {
 
 }
 
+impl From<u8> for NodeType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => NodeType::HP,
+            2 => NodeType::STR,
+            3 => NodeType::MAG,
+            4 => NodeType::ACCESSORY,
+            5 => NodeType::ATB,
+            6 => NodeType::ABILITY,
+            7 => NodeType::ROLE,
+            _ => NodeType::INVALID,
+        }
+    }
+}
+
+impl From<NodeType> for u8 {
+    fn from(value: NodeType) -> Self {
+        match value {
+            NodeType::HP => 1,
+            NodeType::STR => 2,
+            NodeType::MAG => 3,
+            NodeType::ACCESSORY => 4,
+            NodeType::ATB => 5,
+            NodeType::ABILITY => 6,
+            NodeType::ROLE => 7,
+            NodeType::INVALID => 0,
+        }
+    }
+}
+
 impl From<u8> for NodeRole {
     fn from(value: u8) -> Self {
         match value {
@@ -254,6 +629,50 @@ impl From<u8> for NodeRole {
     }
 }
 
+impl NodeRole {
+    pub const ALL: [NodeRole; 6] = [
+        NodeRole::COMMANDO,
+        NodeRole::RAVAGER,
+        NodeRole::SENTINEL,
+        NodeRole::SABOTEUR,
+        NodeRole::SYNERGIST,
+        NodeRole::MEDIC,
+    ];
+}
+
+impl FromStr for NodeType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "HP" => Ok(NodeType::HP),
+            "STR" => Ok(NodeType::STR),
+            "MAG" => Ok(NodeType::MAG),
+            "ACCESSORY" => Ok(NodeType::ACCESSORY),
+            "ATB" => Ok(NodeType::ATB),
+            "ABILITY" => Ok(NodeType::ABILITY),
+            "ROLE" => Ok(NodeType::ROLE),
+            _ => Err(format!("unknown node type {}", value)),
+        }
+    }
+}
+
+impl FromStr for NodeRole {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "COMMANDO" => Ok(NodeRole::COMMANDO),
+            "RAVAGER" => Ok(NodeRole::RAVAGER),
+            "SENTINEL" => Ok(NodeRole::SENTINEL),
+            "SABOTEUR" => Ok(NodeRole::SABOTEUR),
+            "SYNERGIST" => Ok(NodeRole::SYNERGIST),
+            "MEDIC" => Ok(NodeRole::MEDIC),
+            _ => Err(format!("unknown node role {}", value)),
+        }
+    }
+}
+
 impl fmt::Display for NodeRole {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self{
@@ -269,110 +688,121 @@ impl fmt::Display for NodeRole {
 }
 
 impl NodeType{
+    pub const ALL: [NodeType; 7] = [
+        NodeType::HP,
+        NodeType::STR,
+        NodeType::MAG,
+        NodeType::ACCESSORY,
+        NodeType::ATB,
+        NodeType::ABILITY,
+        NodeType::ROLE,
+    ];
+
     pub fn to_imagesrc(&self) -> &str{
         match self {
-            NodeType::HP => "assets/Green Orb.png",
-            NodeType::STR => "assets/Red Orb.png",
-            NodeType::MAG => "assets/Purple Orb.png",
-            NodeType::ACCESSORY => "assets/Orange Orb.png",
-            NodeType::ATB => "assets/White Crystal.png",
-            NodeType::ABILITY => "assets/Yellow Orb.png",
-            NodeType::ROLE => "assets/White Crystal.png",
+            NodeType::HP => "/assets/Green Orb.png",
+            NodeType::STR => "/assets/Red Orb.png",
+            NodeType::MAG => "/assets/Purple Orb.png",
+            NodeType::ACCESSORY => "/assets/Orange Orb.png",
+            NodeType::ATB => "/assets/White Crystal.png",
+            NodeType::ABILITY => "/assets/Yellow Orb.png",
+            NodeType::ROLE => "/assets/White Crystal.png",
             NodeType::INVALID => "",
         }
     }
 }
 
-//Empty implementation for including functions.
-impl ReadUtilities for Crystarium{}
+impl Node {
+    /// Type as shown to the user, unknown types keep their byte visible, e.g. `INVALID 0x09`.
+    pub fn type_label(&self) -> String {
+        match self.node_type {
+            NodeType::INVALID => format!("{} 0x{:02x}", NodeType::INVALID, self.type_byte()),
+            node_type => node_type.to_string(),
+        }
+    }
+
+    /// Role as shown to the user, roles outside of the six known ones keep their value visible as well.
+    pub fn role_label(&self) -> String {
+        match NodeRole::from(self.role) {
+            NodeRole::INVALID => format!("{} 0x{:02x}", NodeRole::INVALID, self.role),
+            role => role.to_string(),
+        }
+    }
+
+    //The raw byte is written as long as the decoded type wasn't changed, so unknown types survive.
+    pub fn type_byte(&self) -> u8 {
+        match NodeType::from(self.type_raw) == self.node_type {
+            true => self.type_raw,
+            false => u8::from(self.node_type),
+        }
+    }
+
+    pub fn stage_role_byte(&self) -> u8 {
+        match (self.stage_role_raw / 16, self.stage_role_raw % 16) == (self.stage, self.role) {
+            true => self.stage_role_raw,
+            false => self.stage * 16 + self.role,
+        }
+    }
+}
 
 impl Crystarium {
-    pub fn create<T: BufRead + Seek + Sized>(reader: &mut T, fstruct: &FileStructure) -> Crystarium {
-        let position = reader.stream_position().unwrap();
+    pub fn create(fstruct: &FileStructure) -> Result<Crystarium, WdbError> {
         let mut crystarium = Crystarium::default();
-        
-        //Going forward to first entry position.
-        let entry = fstruct.entries.iter().filter(|e: &&Entry| !e.name.starts_with('!')).nth(0).unwrap();
-        if position < entry.offset as u64{ 
-            let advance_bytes = entry.offset as u64 - position;
-            io::copy(&mut reader.by_ref().take(advance_bytes), &mut io::sink()).unwrap();
-        }
-
-        for entry in fstruct.entries.iter().filter(|e: &&Entry| !e.name.starts_with('!')){
-
-            let cp_cost = BigEndian::read_i32(&Self::load_part(reader, 4));
-            let _ = BigEndian::read_i32(&Self::load_part(reader, 4));
-            let node_value: i16 = BigEndian::read_i16(&Self::load_part(reader, 2));
-            let mut node_type_vec: Vec<u8> = Vec::new();
-            io::copy(&mut reader.by_ref().take(1), &mut node_type_vec).unwrap();
-            let node_type:NodeType = match node_type_vec[0]{
-                1 => { NodeType::HP },
-                2 => { NodeType::STR },
-                3 => { NodeType::MAG },
-                4 => { NodeType::ACCESSORY },
-                5 => { NodeType::ATB },
-                6 => { NodeType::ABILITY },
-                7 => { NodeType::ROLE },
-                _ => { NodeType::INVALID },   
-            };
 
-            let char_name = match &entry.name[..5] {
-                "cr_fa" => "Fang",
-                "cr_hp" => "Hope",
-                "cr_lt" => "Lightning",
-                "cr_sz" => "Sazh",
-                "cr_sn" => "Snow",
-                "cr_va" => "Vanille",
-                _ => "None",
-            };
-            
-            let mut buf_stagerole: Vec<u8> = Vec::new();
-            io::copy(&mut reader.by_ref().take(1), &mut buf_stagerole).unwrap();
-            let stage = buf_stagerole[0]/16;
-            let role = buf_stagerole[0]%16;
-            
-            crystarium.character = char_name.to_string();
-
-            crystarium.nodes.push(Node{
-                node_name: entry.name.clone(),
-                cp_cost: cp_cost,
-                ability: "ability".to_string(),
-                node_value: node_value,
-                //ability,
-                node_type: node_type,
-                stage: stage,
-                role: role
-        });
-        }    
-
-        crystarium
+        //The layout of the records depends on the game the file belongs to.
+        let layout = fstruct.layout();
+
+        for entry in fstruct.entries.iter().filter(|e: &&Entry| !e.name.starts_with('!')) {
+            //The file structure already holds the raw record of every node.
+            let node = layout.decode(fstruct, entry.name(), &entry.data)?;
+
+            crystarium.character = layout.character(&node.node_name).unwrap_or("None").to_string();
+            crystarium.nodes.push(node);
+        }
+
+        Ok(crystarium)
     }
 }
 
-pub fn read_crystal_wdb_with_file(path: &str) -> Result<Crystarium, &'static str> {
-    let file_h = fs::File::open(path).unwrap();
+pub fn read_crystal_wdb_with_file(path: &str) -> Result<Crystarium, WdbError> {
+    let file_h = fs::File::open(path)?;
 
     //Buffered Reader for file
     let mut b_reader = BufReader::new(file_h);
 
     //File Structure Mapping
-    let fstruct = FileStructure::load(&mut b_reader);
+    let fstruct = FileStructure::load(&mut b_reader)?;
 
     //Using the file structure to get the data for crystal infos.
-    let crystarium = Crystarium::create(&mut b_reader, &fstruct);
+    let crystarium = Crystarium::create(&fstruct)?;
+
+    Ok(crystarium)
+}
+
+pub fn read_crystal_wdb(data: Vec<u8>) -> Result<Crystarium, WdbError> {
+    let (_, crystarium) = read_crystal_wdb_with_structure(data)?;
 
     Ok(crystarium)
 }
 
-pub fn read_crystal_wdb(data: Vec<u8>) -> Result<Crystarium, &'static str> {
+pub fn read_crystal_wdb_with_structure(data: Vec<u8>) -> Result<(FileStructure, Crystarium), WdbError> {
     //Buffered Reader for file
     let mut b_cursor = Cursor::new(data);
 
     //File Structure Mapping
-    let fstruct = FileStructure::load(&mut b_cursor);
+    let fstruct = FileStructure::load(&mut b_cursor)?;
 
     //Using the file structure to get the data for crystal infos.
-    let crystarium = Crystarium::create(&mut b_cursor, &fstruct);
+    let crystarium = Crystarium::create(&fstruct)?;
 
-    Ok(crystarium)
+    Ok((fstruct, crystarium))
 }
+
+pub fn write_crystal_wdb(fstruct: &FileStructure, crystarium: &Crystarium) -> Result<Vec<u8>, WdbError> {
+    let mut data: Vec<u8> = Vec::new();
+
+    //The file structure delivers header and sections, the crystarium the node records.
+    fstruct.write(&mut data, crystarium)?;
+
+    Ok(data)
+}
\ No newline at end of file
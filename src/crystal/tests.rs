@@ -0,0 +1,270 @@
+use super::*;
+
+// Synthetic crystal_*.wdb builder, game files can't be shipped with the repo.
+struct Fixture {
+    strings: Vec<&'static str>,
+    // name, cp cost, string offset, value, type, stage/role byte
+    nodes: Vec<(&'static str, i32, i32, i16, u8, u8)>,
+    // Sections the reader doesn't decode, placed in front of the node records.
+    sections: Vec<(&'static str, Vec<u8>)>,
+    header_padding: [u8; 8],
+    name_padding: u8,
+    entry_padding: u8,
+    record_gap: Vec<u8>,
+    record_tail: Vec<u8>,
+    trailing: Vec<u8>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Fixture {
+            strings: vec!["", "ab_ruin", "ab_fire"],
+            nodes: vec![
+                ("cr_lt0001000000", 0, 0, 0, 7, 0x11),
+                ("cr_lt0101000000", 100, 0, 10, 1, 0x11),
+                ("cr_lt0102000000", 150, 1, 0, 6, 0x12),
+                ("cr_lt0201000000", 300, 9, 3, 2, 0x21),
+            ],
+            sections: Vec::new(),
+            header_padding: [0; 8],
+            name_padding: 0,
+            entry_padding: 0,
+            record_gap: Vec::new(),
+            record_tail: Vec::new(),
+            trailing: Vec::new(),
+        }
+    }
+}
+
+impl Fixture {
+    fn build(&self) -> Vec<u8> {
+        let mut sections: Vec<(&str, Vec<u8>)> = vec![
+            ("!!string", self.strings.iter().flat_map(|s| format!("{}\0", s).into_bytes()).collect()),
+            ("!!strtypelist", vec![0, 1, 0, 0, 0]),
+            ("!!typelist", vec![0, 0, 0, 1, 0, 0, 0, 1]),
+            ("!!version", 1i32.to_be_bytes().to_vec()),
+        ];
+        sections.extend(self.sections.iter().cloned());
+        let first_node = sections.len();
+        for (name, cost, ability, value, node_type, stage_role) in self.nodes.iter() {
+            let mut record = Vec::new();
+            record.extend_from_slice(&cost.to_be_bytes());
+            record.extend_from_slice(&ability.to_be_bytes());
+            record.extend_from_slice(&value.to_be_bytes());
+            record.extend_from_slice(&[*node_type, *stage_role]);
+            record.extend_from_slice(&self.record_tail);
+            sections.push((name, record));
+        }
+
+        let mut header = b"WPD\0".to_vec();
+        header.extend_from_slice(&(sections.len() as i32).to_be_bytes());
+        header.extend_from_slice(&self.header_padding);
+
+        let mut data = Vec::new();
+        let mut offset = 16 + 32 * sections.len();
+        for (index, (name, bytes)) in sections.iter().enumerate() {
+            // The gap goes in front of the first node record.
+            if index == first_node {
+                data.extend_from_slice(&self.record_gap);
+                offset += self.record_gap.len();
+            }
+
+            let mut name_field = format!("{}\0", name).into_bytes();
+            name_field.resize(16, self.name_padding);
+            header.extend_from_slice(&name_field);
+            header.extend_from_slice(&(offset as i32).to_be_bytes());
+            header.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+            header.extend_from_slice(&[self.entry_padding; 8]);
+
+            data.extend_from_slice(bytes);
+            offset += bytes.len();
+        }
+
+        header.extend_from_slice(&data);
+        header.extend_from_slice(&self.trailing);
+        header
+    }
+}
+
+fn round_trip(data: Vec<u8>) {
+    let (fstruct, crystarium) = read_crystal_wdb_with_structure(data.clone()).unwrap();
+    assert_eq!(write_crystal_wdb(&fstruct, &crystarium).unwrap(), data);
+}
+
+#[test]
+fn test_round_trip_plain() {
+    round_trip(Fixture::default().build());
+}
+
+#[test]
+fn test_round_trip_padding() {
+    round_trip(
+        Fixture {
+            header_padding: [1, 2, 3, 4, 5, 6, 7, 8],
+            name_padding: 0xcd,
+            entry_padding: 0xab,
+            ..Default::default()
+        }
+        .build(),
+    );
+}
+
+#[test]
+fn test_round_trip_unknown_bytes() {
+    round_trip(
+        Fixture {
+            record_gap: vec![0, 0, 0xff, 0],
+            record_tail: vec![0xde, 0xad, 0xbe, 0xef],
+            trailing: vec![0x42; 12],
+            ..Default::default()
+        }
+        .build(),
+    );
+}
+
+#[test]
+fn test_round_trip_duplicate_strings() {
+    // Both nodes point to a different copy of the same ability id.
+    round_trip(
+        Fixture {
+            strings: vec!["", "ab_ruin", "ab_ruin"],
+            nodes: vec![("cr_fa0101000000", 80, 9, 0, 6, 0x11), ("cr_fa0102000000", 90, 1, 0, 6, 0x11)],
+            ..Default::default()
+        }
+        .build(),
+    );
+}
+
+#[test]
+fn test_round_trip_mid_string_offset() {
+    // Offset 4 points behind "ab_" into the shared suffix of ab_ruin.
+    let data = Fixture {
+        nodes: vec![("cr_lt0101000000", 80, 4, 0, 6, 0x11), ("cr_lt0102000000", 90, 1, 0, 6, 0x11)],
+        ..Default::default()
+    }
+    .build();
+    round_trip(data.clone());
+
+    let crystarium = read_crystal_wdb(data).unwrap();
+    assert_eq!(crystarium.nodes[0].ability, "ruin");
+    assert_eq!(crystarium.nodes[1].ability, "ab_ruin");
+}
+
+#[test]
+fn test_round_trip_unresolved_offset() {
+    // Offsets outside of the string section decode to an empty ability and are kept as they are.
+    let data = Fixture {
+        nodes: vec![("cr_lt0101000000", 80, -1, 10, 1, 0x11), ("cr_lt0102000000", 90, 1000, 10, 1, 0x11)],
+        ..Default::default()
+    }
+    .build();
+    round_trip(data.clone());
+
+    let crystarium = read_crystal_wdb(data).unwrap();
+    assert!(crystarium.nodes.iter().all(|node| node.ability.is_empty()));
+}
+
+#[test]
+fn test_edit_changes_only_record() {
+    let data = Fixture::default().build();
+    let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(data.clone()).unwrap();
+
+    crystarium.nodes[1].cp_cost = 0x1234;
+    let written = write_crystal_wdb(&fstruct, &crystarium).unwrap();
+
+    let changed: Vec<usize> = (0..data.len()).filter(|i| data[*i] != written[*i]).collect();
+    assert_eq!(written.len(), data.len());
+    assert_eq!(changed.len(), 2);
+    assert_eq!(read_crystal_wdb(written).unwrap().nodes[1].cp_cost, 0x1234);
+}
+
+#[test]
+fn test_new_ability_is_appended() {
+    let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(Fixture::default().build()).unwrap();
+
+    crystarium.nodes[1].ability = "ab_thunder".to_string();
+    let crystarium = read_crystal_wdb(write_crystal_wdb(&fstruct, &crystarium).unwrap()).unwrap();
+
+    assert_eq!(crystarium.nodes[1].ability, "ab_thunder");
+    assert_eq!(crystarium.nodes[2].ability, "ab_ruin");
+    assert_eq!(crystarium.nodes[3].ability, "ab_fire");
+}
+
+#[test]
+fn test_truncated_file() {
+    let data = Fixture::default().build();
+
+    for length in [0, 3, 20, 100, data.len() - 1] {
+        assert!(read_crystal_wdb(data[..length].to_vec()).is_err());
+    }
+}
+
+#[test]
+fn test_unknown_type_is_kept() {
+    let data = Fixture {
+        nodes: vec![("cr_lt0101000000", 100, 0, 10, 9, 0x17)],
+        ..Default::default()
+    }
+    .build();
+    round_trip(data.clone());
+
+    let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(data).unwrap();
+    assert!(matches!(crystarium.nodes[0].node_type, NodeType::INVALID));
+    assert_eq!((crystarium.nodes[0].type_raw, crystarium.nodes[0].stage_role_raw), (9, 0x17));
+
+    // A changed type replaces the unknown byte.
+    crystarium.nodes[0].node_type = NodeType::MAG;
+    let crystarium = read_crystal_wdb(write_crystal_wdb(&fstruct, &crystarium).unwrap()).unwrap();
+    assert_eq!(crystarium.nodes[0].type_raw, 3);
+}
+
+#[test]
+fn test_raw_layout() {
+    let data = Fixture {
+        nodes: vec![("cr_xx0101000000", 100, 0, 10, 1, 0x11), ("cr_xx0102000000", 150, 1, 0, 6, 0x12)],
+        record_tail: vec![0x05, 0x80],
+        ..Default::default()
+    }
+    .build();
+    round_trip(data.clone());
+
+    let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(data).unwrap();
+    assert_eq!(fstruct.layout().name(), "Raw");
+    assert_eq!(crystarium.nodes[1].extra.get("byte_13"), Some(&0x80));
+
+    crystarium.nodes[1].extra.insert("byte_12".to_string(), 7);
+    let crystarium = read_crystal_wdb(write_crystal_wdb(&fstruct, &crystarium).unwrap()).unwrap();
+    assert_eq!(crystarium.nodes[1].extra.get("byte_12"), Some(&7));
+    assert_eq!(crystarium.nodes[0].extra.get("byte_12"), Some(&5));
+}
+
+#[test]
+fn test_unknown_section_is_kept() {
+    let data = Fixture {
+        sections: vec![("!!sheetname", b"crystal\0".to_vec())],
+        ..Default::default()
+    }
+    .build();
+    round_trip(data.clone());
+
+    // The section survives an edit of the nodes as well.
+    let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(data).unwrap();
+    crystarium.nodes[1].ability = "ab_thunder".to_string();
+    let (fstruct, _) = read_crystal_wdb_with_structure(write_crystal_wdb(&fstruct, &crystarium).unwrap()).unwrap();
+    let section = fstruct.entries().iter().find(|entry| entry.name() == "!!sheetname").unwrap();
+    assert_eq!(section.data(), b"crystal\0");
+}
+
+#[test]
+fn test_layout_detection() {
+    // FF13 names win, unknown names are only read as raw bytes when the records are longer than FF13 ones.
+    let layout = |name: &'static str, record_tail: Vec<u8>| {
+        let data = Fixture { nodes: vec![(name, 100, 0, 10, 1, 0x11)], record_tail, ..Default::default() }.build();
+        read_crystal_wdb_with_structure(data).unwrap().0.layout().name()
+    };
+
+    assert_eq!(layout("cr_lt0101000000", Vec::new()), "FF13");
+    assert_eq!(layout("cr_lt0101000000", vec![0x05]), "FF13");
+    assert_eq!(layout("cr_xx0101000000", Vec::new()), "FF13");
+    assert_eq!(layout("cr_xx0101000000", vec![0x05]), "Raw");
+}
@@ -12,11 +12,11 @@ pub struct CrystalPage {
 
 
 impl CrystalPage {
-    pub fn convert(character: &str, node_fragments: &mut Vec<NodeFragment>) -> Vec<Self> {
+    pub fn convert(character: &str, node_fragments: &mut [NodeFragment]) -> Vec<Self> {
         //Test with BtreeMap
         let mut btree_entries: BTreeMap<i16, BTreeMap<String, Vec<NodeFragment>>> = BTreeMap::new();
 
-        for node in node_fragments.clone() {
+        for node in node_fragments.iter() {
             btree_entries
                 .entry(node.stage)
                 .or_default()
@@ -30,14 +30,17 @@ impl CrystalPage {
 
         for entry in btree_entries {
             let mut role_fragments: ListRoleFragment = ListRoleFragment(Vec::<RoleFragment>::new());
-            let mut crystal_page = CrystalPage::default();
-            crystal_page.character = character.to_string();
-            crystal_page.stage = entry.0;
-            
+            let mut crystal_page = CrystalPage {
+                character: character.to_string(),
+                stage: entry.0,
+                ..Default::default()
+            };
+
             for role in entry.1{
-                let mut role_fragment: RoleFragment = RoleFragment::default();
-                role_fragment.name = role.0;
-                role_fragment.nodes = role.1;
+                let role_fragment = RoleFragment {
+                    name: role.0,
+                    nodes: role.1,
+                };
                 role_fragments.0.push(role_fragment.clone());
             }
 
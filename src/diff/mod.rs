@@ -0,0 +1,187 @@
+use crate::crystal::{Crystarium, Node};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    //cost, value, type, stage, role, ability or the name of an extra field.
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeDiff {
+    pub node_name: String,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Diff {
+    pub old_character: String,
+    pub new_character: String,
+    //Names of the new file which the old one doesn't have, in the order of the new file.
+    pub added: Vec<String>,
+    //Names of the old file which are gone, in the order of the old file.
+    pub removed: Vec<String>,
+    pub changed: Vec<NodeDiff>,
+    pub unchanged: usize,
+}
+
+pub fn changes(old: &Node, new: &Node) -> Vec<Change> {
+    let fields = [
+        ("cost", old.cp_cost.to_string(), new.cp_cost.to_string()),
+        ("value", old.node_value.to_string(), new.node_value.to_string()),
+        //Unknown types and roles are shown with their number, so a changed unknown value is still visible.
+        ("type", old.type_label(), new.type_label()),
+        ("stage", old.stage.to_string(), new.stage.to_string()),
+        ("role", old.role_label(), new.role_label()),
+        ("ability", old.ability.clone(), new.ability.clone()),
+    ];
+
+    let mut changes: Vec<Change> = fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| Change { field: field.to_string(), old, new })
+        .collect();
+
+    //Extra fields of other layouts, a field only one side has is shown as empty on the other.
+    let mut extra: BTreeMap<&String, (Option<i64>, Option<i64>)> = BTreeMap::new();
+    for (key, value) in old.extra.iter() {
+        extra.entry(key).or_default().0 = Some(*value);
+    }
+    for (key, value) in new.extra.iter() {
+        extra.entry(key).or_default().1 = Some(*value);
+    }
+    for (key, (old, new)) in extra {
+        if old != new {
+            let text = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+            changes.push(Change { field: key.clone(), old: text(old), new: text(new) });
+        }
+    }
+
+    changes
+}
+
+//Nodes are matched by name, the position inside the file doesn't matter.
+pub fn diff(old: &Crystarium, new: &Crystarium) -> Diff {
+    let old_nodes: BTreeMap<&str, &Node> = old.nodes.iter().map(|node| (node.node_name.as_str(), node)).collect();
+    let new_nodes: BTreeMap<&str, &Node> = new.nodes.iter().map(|node| (node.node_name.as_str(), node)).collect();
+
+    let mut result = Diff {
+        old_character: old.character.clone(),
+        new_character: new.character.clone(),
+        ..Default::default()
+    };
+
+    for node in old.nodes.iter() {
+        match new_nodes.get(node.node_name.as_str()) {
+            None => result.removed.push(node.node_name.clone()),
+            Some(new_node) => {
+                let changes = changes(node, new_node);
+                match changes.is_empty() {
+                    true => result.unchanged += 1,
+                    false => result.changed.push(NodeDiff { node_name: node.node_name.clone(), changes }),
+                }
+            }
+        }
+    }
+
+    result.added = new
+        .nodes
+        .iter()
+        .filter(|node| !old_nodes.contains_key(node.node_name.as_str()))
+        .map(|node| node.node_name.clone())
+        .collect();
+
+    result
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.old_character == self.new_character {
+            true => writeln!(f, "{}", self.old_character)?,
+            false => writeln!(f, "{} -> {}", self.old_character, self.new_character)?,
+        }
+        write!(
+            f,
+            "{} changed, {} added, {} removed, {} unchanged",
+            self.changed.len(),
+            self.added.len(),
+            self.removed.len(),
+            self.unchanged
+        )?;
+
+        for name in self.removed.iter() {
+            write!(f, "\n- {}", name)?;
+        }
+        for name in self.added.iter() {
+            write!(f, "\n+ {}", name)?;
+        }
+        for node in self.changed.iter() {
+            write!(f, "\n~ {}", node.node_name)?;
+            for change in node.changes.iter() {
+                write!(f, "\n    {}: {} -> {}", change.field, change.old, change.new)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_diff() {
+    use crate::crystal::{NodeRole, NodeType};
+
+    let node = |name: &str| Node {
+        node_name: name.to_string(),
+        cp_cost: 100,
+        node_value: 10,
+        stage: 1,
+        role: NodeRole::COMMANDO as u8,
+        ..Default::default()
+    };
+    let old = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![node("cr_lt0101000000"), node("cr_lt0102000000"), node("cr_lt0103000000")],
+    };
+    let new = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node("cr_lt0104000000"),
+            Node { cp_cost: 50, node_type: NodeType::STR, ..node("cr_lt0101000000") },
+            node("cr_lt0102000000"),
+        ],
+    };
+
+    let result = diff(&old, &new);
+    assert_eq!(result.added, vec!["cr_lt0104000000".to_string()]);
+    assert_eq!(result.removed, vec!["cr_lt0103000000".to_string()]);
+    assert_eq!(result.unchanged, 1);
+    assert_eq!(
+        result.changed[0].changes,
+        vec![
+            Change { field: "cost".to_string(), old: "100".to_string(), new: "50".to_string() },
+            Change { field: "type".to_string(), old: "HP".to_string(), new: "STR".to_string() },
+        ]
+    );
+    assert!(diff(&old, &old).is_empty());
+}
+
+#[test]
+fn test_diff_unknown_role() {
+    let old = Node { node_name: "cr_lt0101000000".to_string(), role: 7, ..Default::default() };
+    let new = Node { role: 10, ..old.clone() };
+
+    //Unknown roles keep their number, so the change isn't hidden behind two equal labels.
+    assert_eq!(
+        changes(&old, &new),
+        vec![Change { field: "role".to_string(), old: "INVALID 0x07".to_string(), new: "INVALID 0x0a".to_string() }]
+    );
+}
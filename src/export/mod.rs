@@ -0,0 +1,307 @@
+use crate::crystal::{Crystarium, Node, NodeRole, NodeType};
+use crate::table::Table;
+use std::{collections::HashSet, error, fmt};
+
+#[derive(Debug)]
+pub enum ImportError {
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    MissingColumn(&'static str),
+    InvalidField {
+        line: u64,
+        column: &'static str,
+        message: String,
+    },
+    CharacterMismatch {
+        expected: String,
+        found: String,
+    },
+    InvalidNode {
+        name: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Json(err) => write!(f, "invalid json: {}", err),
+            ImportError::Csv(err) => write!(f, "invalid csv: {}", err),
+            ImportError::MissingColumn(column) => write!(f, "column {} is missing", column),
+            ImportError::InvalidField { line, column, message } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            }
+            ImportError::CharacterMismatch { expected, found } => {
+                write!(f, "crystal of {} can't be imported into the crystal of {}", found, expected)
+            }
+            ImportError::InvalidNode { name, message } => write!(f, "node {}: {}", name, message),
+        }
+    }
+}
+
+impl error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ImportError::Json(err) => Some(err),
+            ImportError::Csv(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(err: serde_json::Error) -> Self {
+        ImportError::Json(err)
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(err: csv::Error) -> Self {
+        ImportError::Csv(err)
+    }
+}
+
+fn invalid(name: &str, message: String) -> ImportError {
+    ImportError::InvalidNode { name: name.to_string(), message }
+}
+
+pub fn to_json(crystarium: &Crystarium) -> String {
+    //Only strings and numbers, serializing can't fail.
+    serde_json::to_string_pretty(crystarium).unwrap()
+}
+
+pub fn from_json(text: &str) -> Result<Crystarium, ImportError> {
+    let crystarium: Crystarium = serde_json::from_str(text)?;
+    validate(&crystarium)?;
+
+    Ok(crystarium)
+}
+
+//Spreadsheet columns, one row per node.
+pub const CSV_COLUMNS: [&str; 7] = ["node_name", "stage", "role", "type", "value", "cp_cost", "ability"];
+
+pub fn to_csv(crystarium: &Crystarium, delimiter: u8) -> String {
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+
+    //Writing into a Vec can't fail.
+    writer.write_record(CSV_COLUMNS).unwrap();
+    for node in crystarium.nodes.iter() {
+        //Unknown roles are written as number, so they survive the re-import.
+        let role = match NodeRole::from(node.role) {
+            NodeRole::INVALID => node.role.to_string(),
+            role => role.to_string(),
+        };
+
+        writer
+            .write_record([
+                node.node_name.clone(),
+                node.stage.to_string(),
+                role,
+                node.node_type.to_string(),
+                node.node_value.to_string(),
+                node.cp_cost.to_string(),
+                node.ability.clone(),
+            ])
+            .unwrap();
+    }
+
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+pub fn table_to_csv(table: &Table, delimiter: u8) -> String {
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).flexible(true).from_writer(Vec::new());
+
+    //Columns are named by position, e.g. field_1.
+    let columns = table.rows.iter().map(|row| row.values.len()).max().unwrap_or_default();
+    let mut header = vec!["name".to_string()];
+    header.extend((0..columns).map(|index| format!("field_{}", index)));
+    writer.write_record(&header).unwrap();
+
+    for row in table.rows.iter() {
+        let mut record = vec![row.name.clone()];
+        record.extend(row.values.iter().map(|value| value.to_string()));
+        writer.write_record(&record).unwrap();
+    }
+
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+fn parse_field<T: std::str::FromStr>(line: u64, column: &'static str, value: &str) -> Result<T, ImportError>
+where
+    T::Err: fmt::Display,
+{
+    value.trim().parse::<T>().map_err(|err| ImportError::InvalidField {
+        line,
+        column,
+        message: format!("{:?}: {}", value, err),
+    })
+}
+
+fn parse_role(line: u64, value: &str) -> Result<u8, ImportError> {
+    //Role names like the export, plain numbers for roles without name.
+    match value.trim().parse::<NodeRole>() {
+        Ok(role) => Ok(role as u8),
+        Err(err) => match value.trim().parse::<u8>() {
+            Ok(role) if role <= 15 => Ok(role),
+            _ => Err(ImportError::InvalidField { line, column: "role", message: err }),
+        },
+    }
+}
+
+fn parse_type(line: u64, value: &str) -> Result<NodeType, ImportError> {
+    match value.trim() {
+        "INVALID" => Ok(NodeType::INVALID),
+        value => value
+            .parse::<NodeType>()
+            .map_err(|message| ImportError::InvalidField { line, column: "type", message }),
+    }
+}
+
+pub fn from_csv(original: &Crystarium, text: &str, delimiter: u8) -> Result<Crystarium, ImportError> {
+    let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(text.as_bytes());
+
+    //Columns are found by header, spreadsheets may reorder them.
+    let headers = reader.headers()?.clone();
+    let mut index = [0; 7];
+    for (position, column) in CSV_COLUMNS.iter().enumerate() {
+        index[position] = headers
+            .iter()
+            .position(|header| header.trim() == *column)
+            .ok_or(ImportError::MissingColumn(column))?;
+    }
+
+    let mut nodes = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |position: usize| record.get(index[position]).unwrap_or_default();
+
+        let node_name = field(0).trim().to_string();
+        if node_name.is_empty() {
+            return Err(ImportError::InvalidField { line, column: "node_name", message: "is empty".to_string() });
+        }
+
+        let stage: u8 = parse_field(line, "stage", field(1))?;
+        if stage > 15 {
+            return Err(ImportError::InvalidField { line, column: "stage", message: format!("{} is out of range", stage) });
+        }
+
+        nodes.push(Node {
+            node_name,
+            stage,
+            role: parse_role(line, field(2))?,
+            node_type: parse_type(line, field(3))?,
+            node_value: parse_field(line, "value", field(4))?,
+            cp_cost: parse_field(line, "cp_cost", field(5))?,
+            ability: field(6).trim().to_string(),
+            ..Default::default()
+        });
+    }
+
+    //The sheet has no raw bytes or extra fields, they are taken from the node of the same name.
+    for node in nodes.iter_mut() {
+        if let Some(known) = original.nodes.iter().find(|n| n.node_name == node.node_name) {
+            node.type_raw = known.type_raw;
+            node.stage_role_raw = known.stage_role_raw;
+            node.extra = known.extra.clone();
+        }
+    }
+
+    //The sheet holds no character, it is the one of the crystal it is imported into.
+    let crystarium = Crystarium { character: original.character.clone(), nodes };
+    validate(&crystarium)?;
+
+    Ok(crystarium)
+}
+
+pub fn validate(crystarium: &Crystarium) -> Result<(), ImportError> {
+    //Checks everything the writer would otherwise reject halfway through.
+    let mut names = HashSet::new();
+
+    for node in crystarium.nodes.iter() {
+        if node.node_name.is_empty() || node.node_name.starts_with('!') {
+            return Err(invalid(&node.node_name, "is not a valid node name".to_string()));
+        }
+        if node.node_name.len() > 15 {
+            return Err(invalid(&node.node_name, "name is longer than 15 bytes".to_string()));
+        }
+        if !names.insert(node.node_name.as_str()) {
+            return Err(invalid(&node.node_name, "appears more than once".to_string()));
+        }
+        if node.ability.contains('\0') {
+            return Err(invalid(&node.node_name, "ability contains a null byte".to_string()));
+        }
+        if node.stage > 15 {
+            return Err(invalid(&node.node_name, format!("stage {} is out of range", node.stage)));
+        }
+        //Role 0 and other unknown roles are kept as read, they only have to fit into the record.
+        if node.role > 15 {
+            return Err(invalid(&node.node_name, format!("role {} is out of range", node.role)));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn import(original: &Crystarium, imported: Crystarium) -> Result<Crystarium, ImportError> {
+    //Imports replace the nodes of the same character only, the file structure stays the one of the original.
+    if imported.character != original.character {
+        return Err(ImportError::CharacterMismatch {
+            expected: original.character.clone(),
+            found: imported.character,
+        });
+    }
+    validate(&imported)?;
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+fn test_crystarium() -> Crystarium {
+    Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![Node {
+            node_name: "cr_lt0101000000".to_string(),
+            cp_cost: 100,
+            ability: "ab_ruin".to_string(),
+            node_value: 0,
+            node_type: NodeType::ABILITY,
+            stage: 1,
+            role: NodeRole::RAVAGER as u8,
+            type_raw: 6,
+            stage_role_raw: 0x12,
+            ..Default::default()
+        }],
+    }
+}
+
+#[test]
+fn test_json_round_trip() {
+    let crystarium = test_crystarium();
+
+    let imported = import(&crystarium, from_json(&to_json(&crystarium)).unwrap()).unwrap();
+    assert_eq!(imported.nodes[0].ability, "ab_ruin");
+    assert_eq!(imported.nodes[0].role, NodeRole::RAVAGER as u8);
+
+    let other = Crystarium { character: "Fang".to_string(), ..crystarium.clone() };
+    assert!(import(&other, crystarium).is_err());
+}
+
+#[test]
+fn test_csv_round_trip() {
+    let crystarium = test_crystarium();
+
+    for delimiter in [b',', b'\t'] {
+        let imported = from_csv(&crystarium, &to_csv(&crystarium, delimiter), delimiter).unwrap();
+        assert_eq!(imported.character, "Lightning");
+        assert_eq!(imported.nodes[0].ability, "ab_ruin");
+        assert_eq!(imported.nodes[0].role, NodeRole::RAVAGER as u8);
+        assert_eq!(imported.nodes[0].type_raw, 6);
+    }
+
+    let text = to_csv(&crystarium, b',').replace("RAVAGER", "BERSERKER");
+    match from_csv(&crystarium, &text, b',') {
+        Err(ImportError::InvalidField { line: 2, column: "role", .. }) => (),
+        result => panic!("unexpected {:?}", result),
+    }
+}
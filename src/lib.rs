@@ -0,0 +1,39 @@
+//! Reader and writer for Final Fantasy XIII `crystal_*.wdb` files.
+//!
+//! The parser lives in [`crystal`], export and import in [`export`], the consistency checks in
+//! [`validation`], CP and stat statistics and the playthrough simulator in [`analysis`], the purchase
+//! planner in [`optimizer`], the comparison of two files in [`diff`], text patches in [`patch`], the
+//! seeded shuffle in [`randomizer`], bulk cost and value changes in [`rebalance`] and a generic decoder
+//! for any WDB database in [`table`], none of them has web dependencies. The htmx viewer (pages, templates and sessions) is only built with the `web` feature.
+//!
+//! ```no_run
+//! use ff13_crystal_re::{read_crystal_wdb_with_structure, write_crystal_wdb};
+//!
+//! let data = std::fs::read("crystal_lt.wdb").unwrap();
+//! let (fstruct, mut crystarium) = read_crystal_wdb_with_structure(data).unwrap();
+//! crystarium.nodes[0].cp_cost = 100;
+//! std::fs::write("crystal_lt_mod.wdb", write_crystal_wdb(&fstruct, &crystarium).unwrap()).unwrap();
+//! ```
+
+pub mod analysis;
+pub mod crystal;
+pub mod diff;
+pub mod export;
+pub mod optimizer;
+pub mod patch;
+pub mod randomizer;
+pub mod rebalance;
+pub mod table;
+pub mod validation;
+
+#[cfg(feature = "web")]
+pub mod crystal_page;
+#[cfg(feature = "web")]
+pub mod session;
+#[cfg(feature = "web")]
+pub mod view;
+
+pub use crystal::{
+    read_crystal_wdb, read_crystal_wdb_with_file, read_crystal_wdb_with_structure, write_crystal_wdb, Crystarium,
+    Entry, FileStructure, Layout, Node, NodeRole, NodeType, WdbError,
+};
@@ -1,64 +1,90 @@
-pub mod crystal;
-pub mod crystal_page;
-pub mod view;
-
-use crystal::{read_crystal_wdb, Crystarium};
-use crystal_page::CrystalPage;
+mod cli;
+
+use clap::Parser;
+use cli::{Cli, Command};
+use ff13_crystal_re::{
+    analysis::analyze,
+    crystal::{read_crystal_wdb_with_structure, write_crystal_wdb},
+    export::{to_csv, to_json},
+    rebalance::rebalance,
+    session::{session_cookie, session_id, SessionStore},
+    validation,
+    view::{
+        charts, AnalysisTemplate, ChartTemplate, CrystalData, DiffForm, DiffPage, DiffTemplate, ExportQuery, Index, NodeForm, NodeFormTemplate, NodeFragment, NodeTemplate, NodeViewer,
+        OverviewTemplate, RebalanceForm, RebalanceTemplate, UploadForm,
+    },
+};
 // use log::info;
-use view::{ConvertVecNode, CrystalData, Index, NodeFragment, NodeViewer, UploadForm};
 
 use actix_files::Files;
 use actix_multipart::form::MultipartForm;
 use actix_web::{
-    http::header, web::{self, resource, Data}, App, HttpRequest, HttpResponse, HttpServer, Responder, Result
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType}, web::{self, resource}, App, HttpRequest, HttpResponse, HttpServer, Responder, Result
 };
 use askama::Template;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 
 use std::{
-    io::Read, sync::{Arc, Mutex}, vec::Vec
+    io::Read, path::Path, vec::Vec
 };
 
-use lazy_static::lazy_static;
+fn redirect_index() -> HttpResponse {
+    HttpResponse::PermanentRedirect()
+        .append_header(("Location", "/"))
+        .insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"))
+        .insert_header((header::PRAGMA, "no-cache"))
+        .insert_header((header::EXPIRES, "0"))
+        .finish()
+}
 
-// Define a static variable using lazy_static
-lazy_static! {
-    static ref VIEWER_PAGES: Mutex<Arc<Vec<CrystalPage>>> =
-        Mutex::new(Arc::new(Vec::<CrystalPage>::new()));
+//Names of unknown crystals come from the file name, which may contain spaces, '?', '#' or '/'.
+fn viewer_url(character: &str, query: &str) -> String {
+    format!("/node_viewer/{}?{}", utf8_percent_encode(character, NON_ALPHANUMERIC), query)
+}
+
+async fn node_viewer_first(req: HttpRequest, sessions: web::Data<SessionStore>) -> Result<impl Responder> {
+    //Without character open the first one of the workspace.
+    let first = sessions
+        .with_session(session_id(&req).as_deref(), |session| session.characters().first().cloned())
+        .flatten();
+
+    match first {
+        Some(character) => Ok(HttpResponse::SeeOther()
+            .append_header(("Location", viewer_url(&character, req.query_string())))
+            .finish()),
+        None => Ok(redirect_index()),
+    }
 }
 
 async fn node_viewer(
     req: HttpRequest,
-    data: web::Data<Mutex<CrystalData>>,
+    path: web::Path<String>,
+    sessions: web::Data<SessionStore>,
 ) -> Result<impl Responder> {
     // log::info!("got Node Viewer");
-
-    //first check if static variable has data, if not, get from mutex.
-    let mut guard_pages = VIEWER_PAGES.lock().unwrap();
-    let mut paged_nodes: Vec<CrystalPage> = guard_pages.as_ref().clone();
-
-    if paged_nodes.is_empty() {
-        let guard_crystal_data = data.lock().unwrap();
-        let mut nodes: Vec<NodeFragment> = guard_crystal_data.crystal_data.nodes.clone().convert();
-
-        if nodes.is_empty() {
-            return Ok(HttpResponse::PermanentRedirect()
-                .append_header(("Location", "/"))
-                .insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"))
-                .insert_header((header::PRAGMA, "no-cache"))
-                .insert_header((header::EXPIRES, "0"))
-                .finish());
-        }
-
-        *guard_pages = Arc::new(
-            CrystalPage::convert(&guard_crystal_data.crystal_data.character, &mut nodes).clone(),
-        );
-        paged_nodes = guard_pages.to_owned().to_vec();
-    }
+    let character = path.into_inner();
+
+    //Pages of the session are cached, if the character wasn't uploaded in this session go back to upload.
+    let (paged_nodes, characters, issues) = match sessions.with_session(session_id(&req).as_deref(), |session| {
+        //Validation report of the whole crystal, shown above the stage.
+        let issues: Vec<String> = session
+            .workspace
+            .get(&character)
+            .map(|crystal_data| validation::validate(&crystal_data.file_structure, &crystal_data.crystal_data))
+            .unwrap_or_default()
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+
+        (session.pages(&character).cloned(), session.characters(), issues)
+    }) {
+        Some((Some(paged_nodes), characters, issues)) if !paged_nodes.is_empty() => (paged_nodes, characters, issues),
+        _ => return Ok(redirect_index()),
+    };
 
     //extract query data - page
     let query = req
         .query_string()
-        .to_string()
         .split("page=")
         .last()
         .unwrap()
@@ -85,25 +111,384 @@ async fn node_viewer(
                 .insert_header((header::EXPIRES, "0"))
                 .body(
                     NodeViewer {
-                        character: paged_node.character.clone(),
+                        character,
+                        characters,
                         current_page: paged_node.stage,
-                        prev_page: prev_page,
-                        next_page: next_page,
+                        prev_page,
+                        next_page,
                         roles: paged_node.roles.clone(),
+                        issues,
                     }
                     .render()
                     .unwrap(),
-                )
-                .into();
+                );
 
-            return Ok(response);
+            Ok(response)
         }
         None => {
-            return Ok(HttpResponse::Ok().finish());
+            Ok(HttpResponse::Ok().finish())
+        }
+    }
+}
+
+async fn node(req: HttpRequest, path: web::Path<String>, sessions: web::Data<SessionStore>) -> Result<impl Responder> {
+    let name = path.into_inner();
+
+    let node = sessions
+        .with_session(session_id(&req).as_deref(), |session| session.find_node(&name).cloned())
+        .flatten();
+
+    match node {
+        Some(node) => Ok(HttpResponse::Ok().body(
+            NodeTemplate {
+                node: NodeFragment::from(node),
+            }
+            .render()
+            .unwrap(),
+        )),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+async fn node_form(req: HttpRequest, path: web::Path<String>, sessions: web::Data<SessionStore>) -> Result<impl Responder> {
+    let name = path.into_inner();
+
+    let node = sessions
+        .with_session(session_id(&req).as_deref(), |session| session.find_node(&name).cloned())
+        .flatten();
+
+    match node {
+        Some(node) => Ok(HttpResponse::Ok().body(
+            NodeFormTemplate::new(NodeFragment::from(node))
+                .render()
+                .unwrap(),
+        )),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+async fn node_update(
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<NodeForm>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let name = path.into_inner();
+
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        let (character, node) = match session.find_node_mut(&name) {
+            Some(found) => found,
+            None => return HttpResponse::NotFound().finish(),
+        };
+
+        let (stage, role) = (node.stage, node.role);
+        if let Err(err) = form.apply(node) {
+            return HttpResponse::BadRequest().body(err);
+        }
+
+        let mut response = HttpResponse::Ok();
+        response.insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"));
+
+        //A node moved to another stage or role doesn't belong to this part of the page anymore.
+        if stage != node.stage || role != node.role {
+            response.insert_header(("HX-Refresh", "true"));
+        }
+
+        let body = NodeTemplate {
+            node: NodeFragment::from(node.clone()),
+        }
+        .render()
+        .unwrap();
+
+        //Pages are built from the old node data, rebuild them on the next view.
+        session.invalidate_pages(&character);
+
+        response.body(body)
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().finish()))
+}
+
+async fn download(
+    req: HttpRequest,
+    path: web::Path<String>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        let crystal_data = match session.workspace.get(&character) {
+            Some(crystal_data) => crystal_data,
+            None => return HttpResponse::NotFound().body("No crystal file uploaded."),
+        };
+
+        //Write the edited crystarium back into the structure of the uploaded file.
+        let bytes = match write_crystal_wdb(&crystal_data.file_structure, &crystal_data.crystal_data) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                return HttpResponse::UnprocessableEntity().body(format!("Crystal file can't be written: {}", err))
+            }
+        };
+
+        HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(crystal_data.download_name())],
+            })
+            .body(bytes)
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().body("No crystal file uploaded.")))
+}
+
+async fn overview(
+    req: HttpRequest,
+    path: web::Path<String>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let template = sessions
+        .with_session(session_id(&req).as_deref(), |session| {
+            let crystal_data = session.workspace.get(&character)?;
+            Some(OverviewTemplate::new(character.clone(), session.characters(), &crystal_data.crystal_data))
+        })
+        .flatten();
+
+    match template {
+        Some(template) => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"))
+            .body(template.render().unwrap())),
+        None => Ok(redirect_index()),
+    }
+}
+
+async fn analysis(
+    req: HttpRequest,
+    path: web::Path<String>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let template = sessions
+        .with_session(session_id(&req).as_deref(), |session| {
+            let crystal_data = session.workspace.get(&character)?;
+            let analysis = analyze(&crystal_data.crystal_data);
+            Some(AnalysisTemplate {
+                character: character.clone(),
+                characters: session.characters(),
+                charts: charts(&analysis),
+                analysis,
+            })
+        })
+        .flatten();
+
+    match template {
+        Some(template) => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"))
+            .body(template.render().unwrap())),
+        None => Ok(redirect_index()),
+    }
+}
+
+async fn rebalance_page(
+    req: HttpRequest,
+    path: web::Path<String>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let template = sessions
+        .with_session(session_id(&req).as_deref(), |session| {
+            let crystal_data = session.workspace.get(&character)?;
+            Some(RebalanceTemplate::new(character.clone(), session.characters(), &crystal_data.crystal_data))
+        })
+        .flatten();
+
+    match template {
+        Some(template) => Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"))
+            .body(template.render().unwrap())),
+        None => Ok(redirect_index()),
+    }
+}
+
+async fn rebalance_preview(
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<RebalanceForm>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        let crystal_data = match session.workspace.get(&character) {
+            Some(crystal_data) => crystal_data,
+            None => return HttpResponse::NotFound().finish(),
+        };
+
+        //The workspace stays untouched, only the differences are shown.
+        let rebalanced = form.transform().and_then(|transform| rebalance(&crystal_data.crystal_data, &[transform]));
+        let rebalanced = match rebalanced {
+            Ok(rebalanced) => rebalanced,
+            Err(err) => return HttpResponse::BadRequest().body(err),
+        };
+
+        let template = DiffTemplate::new(
+            character.clone(),
+            format!("{} rebalanced", character),
+            &crystal_data.crystal_data,
+            &rebalanced,
+        );
+        HttpResponse::Ok().body(template.render().unwrap())
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().finish()))
+}
+
+async fn rebalance_apply(
+    req: HttpRequest,
+    path: web::Path<String>,
+    form: web::Form<RebalanceForm>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        let crystal_data = match session.workspace.get_mut(&character) {
+            Some(crystal_data) => crystal_data,
+            None => return HttpResponse::NotFound().finish(),
+        };
+
+        match form.transform().and_then(|transform| rebalance(&crystal_data.crystal_data, &[transform])) {
+            Ok(rebalanced) => crystal_data.crystal_data = rebalanced,
+            Err(err) => return HttpResponse::BadRequest().body(err),
         }
+
+        //Pages are built from the old node data, rebuild them on the next view.
+        session.invalidate_pages(&character);
+
+        HttpResponse::Ok()
+            .insert_header(("HX-Redirect", viewer_url(&character, "page=1")))
+            .finish()
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().finish()))
+}
+
+async fn chart(req: HttpRequest, path: web::Path<String>, sessions: web::Data<SessionStore>) -> Result<impl Responder> {
+    let character = path.into_inner();
+
+    let charts = sessions
+        .with_session(session_id(&req).as_deref(), |session| {
+            let crystal_data = session.workspace.get(&character)?;
+            Some(charts(&analyze(&crystal_data.crystal_data)))
+        })
+        .flatten();
+
+    match charts {
+        Some(charts) => Ok(HttpResponse::Ok().body(ChartTemplate { charts }.render().unwrap())),
+        None => Ok(HttpResponse::NotFound().finish()),
     }
 }
 
+async fn analysis_json(
+    req: HttpRequest,
+    query: web::Query<ExportQuery>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        //Without character the first one of the workspace is analyzed.
+        let crystal_data = match &query.character {
+            Some(character) => session.workspace.get(character),
+            None => session.workspace.values().next(),
+        };
+
+        match crystal_data {
+            Some(crystal_data) => HttpResponse::Ok().json(analyze(&crystal_data.crystal_data)),
+            None => HttpResponse::NotFound().body("No crystal file uploaded."),
+        }
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().body("No crystal file uploaded.")))
+}
+
+async fn export(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<ExportQuery>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
+    let format = path.into_inner();
+    let content_type = match format.as_str() {
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        _ => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let response = sessions.with_session(session_id(&req).as_deref(), |session| {
+        //Without character the first one of the workspace is exported.
+        let crystal_data = match &query.character {
+            Some(character) => session.workspace.get(character),
+            None => session.workspace.values().next(),
+        };
+
+        match crystal_data {
+            Some(crystal_data) => HttpResponse::Ok()
+                .content_type(content_type)
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(
+                        Path::new(&crystal_data.download_name()).with_extension(&format).to_string_lossy().to_string(),
+                    )],
+                })
+                .body(match format.as_str() {
+                    "csv" => to_csv(&crystal_data.crystal_data, b','),
+                    "tsv" => to_csv(&crystal_data.crystal_data, b'\t'),
+                    _ => to_json(&crystal_data.crystal_data),
+                }),
+            None => HttpResponse::NotFound().body("No crystal file uploaded."),
+        }
+    });
+
+    Ok(response.unwrap_or_else(|| HttpResponse::NotFound().body("No crystal file uploaded.")))
+}
+
+async fn diff_page(_req: HttpRequest) -> Result<impl Responder> {
+    Ok(HttpResponse::Ok().body(DiffPage.render().unwrap()))
+}
+
+async fn diff_upload(form: MultipartForm<DiffForm>) -> Result<impl Responder> {
+    //Both files are only read for the comparison, nothing is added to the workspace.
+    let form = form.into_inner();
+    let mut parsed = Vec::new();
+    for mut file in [form.old, form.new] {
+        let mut data: Vec<u8> = Vec::new();
+        file.file.read_to_end(&mut data)?;
+
+        let file_name = file
+            .file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        match read_crystal_wdb_with_structure(data) {
+            Ok((_, crystarium)) => parsed.push((file_name, crystarium)),
+            Err(err) => {
+                return Ok(HttpResponse::BadRequest().body(format!("Invalid crystal file {}: {}", file_name, err)))
+            }
+        }
+    }
+
+    let (new_name, new) = parsed.pop().unwrap();
+    let (old_name, old) = parsed.pop().unwrap();
+
+    Ok(HttpResponse::Ok().body(DiffTemplate::new(old_name, new_name, &old, &new).render().unwrap()))
+}
+
 async fn index(_req: HttpRequest) -> Result<impl Responder> {
     // log::info!("got Index");
     Ok(Into::<HttpResponse>::into(
@@ -111,66 +496,118 @@ async fn index(_req: HttpRequest) -> Result<impl Responder> {
     ))
 }
 
-async fn upload(req: HttpRequest, mut form: MultipartForm<UploadForm>) -> Result<impl Responder> {
+async fn upload(
+    req: HttpRequest,
+    mut form: MultipartForm<UploadForm>,
+    sessions: web::Data<SessionStore>,
+) -> Result<impl Responder> {
     // log::info!("got Upload");
 
-    //Mutex lock and crystal data prepare for file write
-    let mg_crystal_data = req.app_data::<Data<Mutex<CrystalData>>>().unwrap(); //data.lock().unwrap().crystal_data.to_owned();
-    let mut crystal_data = mg_crystal_data.lock().unwrap();
-    
-    //Initialize data at upload
-    crystal_data.crystal_data = Crystarium::default();
-    let mut guard_pages = VIEWER_PAGES.lock().unwrap();
-    let pages_arc = Arc::make_mut(&mut *guard_pages); // Arc mutably dereferenzieren
-    pages_arc.clear(); // Den Vektor leeren
-
-    //Get first uploaded file.
-    let f = form.files.first_mut();
-
     //if no file given, then respond with status code 415
-    if f.is_none() {
+    if form.files.is_empty() {
         return Ok(HttpResponse::UnsupportedMediaType().finish());
     }
 
-    let f = f.unwrap();
-
-    // log::info!("Filename {}", f.file_name.as_ref().unwrap());
-
-    //Declare buffer for file's content
-    let mut data: Vec<u8> = Vec::new();
-
-    //Read content to buffer
-    f.file.read_to_end(&mut data).unwrap();
+    let mut uploads: Vec<CrystalData> = Vec::new();
+
+    for f in form.files.iter_mut() {
+        // log::info!("Filename {}", f.file_name.as_ref().unwrap());
+
+        //Declare buffer for file's content
+        let mut data: Vec<u8> = Vec::new();
+
+        //Read content to buffer
+        f.file.read_to_end(&mut data)?;
+
+        //Keep only the file name of the upload, it is handed back on download.
+        let file_name = f
+            .file_name
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        //Parse crystal data first, one malformed file rejects the whole upload with 400.
+        let (file_structure, crystarium) = match read_crystal_wdb_with_structure(data) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                return Ok(HttpResponse::BadRequest().body(format!("Invalid crystal file {}: {}", file_name, err)))
+            }
+        };
+
+        uploads.push(CrystalData {
+            crystal_data: crystarium,
+            file_structure,
+            file_name,
+        });
+    }
 
-    //Parse crystal data
-    crystal_data.crystal_data = read_crystal_wdb(data).unwrap().clone();
-    // let dbg_file = File::create("debug_contents.txt");
-    // dbg_file.unwrap().write_all(format!("{:?}",crystal_data.crystal_data.clone().nodes).as_bytes());
+    //Uploads are added to the workspace of the session, a new visitor gets a new session.
+    let character = uploads[0].character();
+    let id = sessions.upload(session_id(&req).as_deref(), uploads);
 
     Ok(HttpResponse::Ok()
-        .insert_header(("HX-Redirect", "/node_viewer?page=1"))
+        .cookie(session_cookie(&id))
+        .insert_header(("HX-Redirect", viewer_url(&character, "page=1")))
         .finish())
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    //Without subcommand the web server is started like before.
+    let (host, port) = match Cli::parse().command {
+        None => ("127.0.0.1".to_string(), 8000),
+        Some(Command::Serve { host, port }) => (host, port),
+        Some(command) => {
+            if let Err(err) = cli::run(command) {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    };
+
     // env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     // log::info!("starting HTTP server at http://127.0.0.1:8000");
 
-    let crystal_data = web::Data::new(Mutex::new(CrystalData::default()));
+    let sessions = web::Data::new(SessionStore::default());
+
+    println!("Running on: http://{}:{}/", host, port);
 
-    println!("Running on: http://127.0.0.1:8000/");
-    
     HttpServer::new(move || {
         App::new()
-            .app_data(crystal_data.clone())
+            .app_data(sessions.clone())
             // .wrap(middleware::Logger::default())
             .service(resource("/upload").route(web::post().to(upload)))
-            .service(resource("/node_viewer").route(web::get().to(node_viewer)))
+            .service(resource("/node_viewer").route(web::get().to(node_viewer_first)))
+            .service(resource("/node_viewer/{character}").route(web::get().to(node_viewer)))
+            .service(
+                resource("/node/{name}")
+                    .route(web::get().to(node))
+                    .route(web::patch().to(node_update)),
+            )
+            .service(resource("/node/{name}/edit").route(web::get().to(node_form)))
+            .service(resource("/download/{character}").route(web::get().to(download)))
+            .service(resource("/overview/{character}").route(web::get().to(overview)))
+            .service(resource("/analysis/{character}").route(web::get().to(analysis)))
+            .service(
+                resource("/rebalance/{character}")
+                    .route(web::get().to(rebalance_page))
+                    .route(web::post().to(rebalance_apply)),
+            )
+            .service(resource("/rebalance/{character}/preview").route(web::post().to(rebalance_preview)))
+            .service(resource("/chart/{character}").route(web::get().to(chart)))
+            .service(resource("/analysis.json").route(web::get().to(analysis_json)))
+            .service(resource("/export.{format}").route(web::get().to(export)))
+            .service(
+                resource("/diff")
+                    .route(web::get().to(diff_page))
+                    .route(web::post().to(diff_upload)),
+            )
             .service(resource("/").route(web::get().to(index)))
             .service(Files::new("/assets", "./templates/assets"))
     })
-    .bind(("127.0.0.1", 8000))?
+    .bind((host, port))?
     .run()
     .await
     .expect("Error Server Listening");
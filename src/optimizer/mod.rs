@@ -0,0 +1,227 @@
+use crate::crystal::{Crystarium, Node, NodeRole, NodeType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+//Score per point of HP/STR/MAG and per ATB, accessory, ability or role node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Weights {
+    pub hp: f64,
+    pub strength: f64,
+    pub magic: f64,
+    pub ability: f64,
+    pub atb: f64,
+    pub accessory: f64,
+    pub role: f64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Step {
+    pub node_name: String,
+    pub role: String,
+    pub stage: u8,
+    pub cp_cost: i32,
+    pub cumulative_cp: i64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Plan {
+    pub budget: i64,
+    pub spent: i64,
+    pub score: f64,
+    pub hp: i64,
+    pub strength: i64,
+    pub magic: i64,
+    pub abilities: Vec<String>,
+    pub steps: Vec<Step>,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            hp: 1.0,
+            strength: 1.0,
+            magic: 1.0,
+            ability: 0.0,
+            atb: 0.0,
+            accessory: 0.0,
+            role: 0.0,
+        }
+    }
+}
+
+impl Weights {
+    pub fn score(&self, node: &Node) -> f64 {
+        let value = f64::from(node.node_value);
+
+        match node.node_type {
+            NodeType::HP => self.hp * value,
+            NodeType::STR => self.strength * value,
+            NodeType::MAG => self.magic * value,
+            NodeType::ABILITY => self.ability,
+            NodeType::ATB => self.atb,
+            NodeType::ACCESSORY => self.accessory,
+            NodeType::ROLE => self.role,
+            NodeType::INVALID => 0.0,
+        }
+    }
+}
+
+//Nodes of every role in the order they have to be bought, up to the last unlocked stage.
+pub fn role_paths(crystarium: &Crystarium, max_stage: u8) -> Vec<Vec<&Node>> {
+    let mut paths: BTreeMap<u8, Vec<&Node>> = BTreeMap::new();
+    for node in crystarium.nodes.iter() {
+        paths.entry(node.role).or_default().push(node);
+    }
+
+    paths
+        .into_values()
+        .map(|mut path| {
+            //Stable sort, nodes of the same stage keep the order of the file.
+            path.sort_by_key(|node| node.stage);
+            path.into_iter().take_while(|node| node.stage <= max_stage).collect()
+        })
+        .collect()
+}
+
+//Cost, score and bought nodes per role of one combination.
+type Choice = (i64, f64, Vec<usize>);
+
+fn pareto(mut choices: Vec<Choice>) -> Vec<Choice> {
+    //Cheapest first, a choice is only kept if it scores higher than every cheaper one.
+    choices.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+
+    let mut frontier: Vec<Choice> = Vec::new();
+    for choice in choices {
+        if frontier.last().is_none_or(|best| choice.1 > best.1) {
+            frontier.push(choice);
+        }
+    }
+
+    frontier
+}
+
+pub fn optimize(crystarium: &Crystarium, budget: i64, weights: &Weights, max_stage: u8) -> Plan {
+    let paths = role_paths(crystarium, max_stage);
+
+    //Every role is a chain, so a role contributes one of its prefixes. Merging the roles one by one and
+    //dropping dominated combinations keeps the search exact without a table over the whole budget.
+    let mut frontier: Vec<Choice> = vec![(0, 0.0, Vec::new())];
+    for path in paths.iter() {
+        let mut prefixes = vec![(0i64, 0.0)];
+        for node in path.iter() {
+            let (cost, score) = prefixes[prefixes.len() - 1];
+            prefixes.push((cost + i64::from(node.cp_cost), score + weights.score(node)));
+        }
+
+        let mut merged = Vec::new();
+        for (cost, score, bought) in frontier.iter() {
+            for (count, (prefix_cost, prefix_score)) in prefixes.iter().enumerate() {
+                if cost + prefix_cost <= budget {
+                    let mut bought = bought.clone();
+                    bought.push(count);
+                    merged.push((cost + prefix_cost, score + prefix_score, bought));
+                }
+            }
+        }
+        frontier = pareto(merged);
+    }
+
+    //A negative budget doesn't even allow buying nothing, the plan stays empty.
+    let Some((_, score, bought)) = frontier.pop() else {
+        return Plan { budget, ..Default::default() };
+    };
+    let mut plan = Plan { budget, score, ..Default::default() };
+
+    //Buy the chosen nodes with the best score per CP first, each role still in path order.
+    let mut next = vec![0; paths.len()];
+    loop {
+        let candidate = (0..paths.len()).filter(|role| next[*role] < bought[*role]).max_by(|a, b| {
+            let ratio = |role: usize| {
+                let node = paths[role][next[role]];
+                weights.score(node) / f64::from(node.cp_cost.max(1))
+            };
+            ratio(*a).total_cmp(&ratio(*b)).then(b.cmp(a))
+        });
+        let Some(role) = candidate else {
+            break;
+        };
+
+        let node = paths[role][next[role]];
+        next[role] += 1;
+        plan.spent += i64::from(node.cp_cost);
+
+        match node.node_type {
+            NodeType::HP => plan.hp += i64::from(node.node_value),
+            NodeType::STR => plan.strength += i64::from(node.node_value),
+            NodeType::MAG => plan.magic += i64::from(node.node_value),
+            NodeType::ABILITY => plan.abilities.push(node.ability.clone()),
+            _ => (),
+        }
+
+        plan.steps.push(Step {
+            node_name: node.node_name.clone(),
+            role: NodeRole::from(node.role).to_string(),
+            stage: node.stage,
+            cp_cost: node.cp_cost,
+            cumulative_cp: plan.spent,
+        });
+    }
+
+    plan
+}
+
+#[test]
+fn test_optimize() {
+    let node = |name: &str, role: NodeRole, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        node_name: name.to_string(),
+        stage: 1,
+        role: role as u8,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            //A cheap weak node in front of a strong one, greedy would take the medic node instead.
+            node("cr_lt0101000000", NodeRole::COMMANDO, NodeType::STR, 1, 50),
+            node("cr_lt0102000000", NodeRole::COMMANDO, NodeType::STR, 30, 50),
+            node("cr_lt0103000000", NodeRole::MEDIC, NodeType::MAG, 10, 40),
+            Node { stage: 2, ..node("cr_lt0201000000", NodeRole::MEDIC, NodeType::MAG, 50, 10) },
+        ],
+    };
+
+    let plan = optimize(&crystarium, 100, &Weights::default(), 1);
+    assert_eq!((plan.spent, plan.strength, plan.magic), (100, 31, 0));
+    assert_eq!(plan.steps[1].node_name, "cr_lt0102000000");
+    assert_eq!(plan.steps[1].cumulative_cp, 100);
+
+    //Stage 2 unlocked, the medic path is worth more.
+    let plan = optimize(&crystarium, 60, &Weights::default(), 2);
+    assert_eq!((plan.spent, plan.magic), (50, 60));
+}
+
+#[test]
+fn test_optimize_small_budget() {
+    let node = |name: &str, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        node_name: name.to_string(),
+        stage: 1,
+        role: NodeRole::COMMANDO as u8,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![node("cr_lt0001000000", NodeType::ROLE, 0, 0), node("cr_lt0101000000", NodeType::HP, 20, 40)],
+    };
+
+    let plan = optimize(&crystarium, -1, &Weights::default(), 15);
+    assert_eq!((plan.budget, plan.spent, plan.steps.len()), (-1, 0, 0));
+
+    //Nodes without cost still fit into a budget of zero.
+    let plan = optimize(&crystarium, 0, &Weights { role: 1.0, ..Default::default() }, 15);
+    assert_eq!((plan.spent, plan.hp, plan.steps.len()), (0, 0, 1));
+}
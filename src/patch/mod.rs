@@ -0,0 +1,379 @@
+use crate::crystal::{Crystarium, Node, NodeRole, NodeType};
+use crate::diff::diff;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error, fmt};
+
+// Patch file
+//     character = "Lightning"                  optional, the crystal has to be the one of this character
+//     [[node]]
+//     name = "cr_lt0101000000"
+//     set = { cost = 50, type = "STR" }         new values
+//     expect = { cost = 100, type = "HP" }      optional, values the node must have before
+// Fields: cost, value, type, stage, role, ability and extra (bytes of the raw layout, e.g. { byte_12 = 5 }).
+// Types and roles are names, unknown ones are written as number.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character: Option<String>,
+    #[serde(default, rename = "node")]
+    pub nodes: Vec<NodePatch>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodePatch {
+    pub name: String,
+    pub set: Fields,
+    #[serde(default, skip_serializing_if = "Fields::is_empty")]
+    pub expect: Fields,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<i16>,
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub node_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ability: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, i64>,
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    Toml(toml::de::Error),
+    CharacterMismatch {
+        expected: String,
+        found: String,
+    },
+    UnknownNode(String),
+    DuplicateNode(String),
+    InvalidField {
+        name: String,
+        field: String,
+        message: String,
+    },
+    //The node doesn't have the value the patch expects, it was made for another version of the file.
+    Conflict {
+        name: String,
+        field: String,
+        expected: String,
+        found: String,
+    },
+    Unsupported(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Toml(err) => write!(f, "invalid patch: {}", err),
+            PatchError::CharacterMismatch { expected, found } => {
+                write!(f, "patch for {} can't be applied to the crystal of {}", expected, found)
+            }
+            PatchError::UnknownNode(name) => write!(f, "node {} is not in the crystal", name),
+            PatchError::DuplicateNode(name) => write!(f, "node {} is patched more than once", name),
+            PatchError::InvalidField { name, field, message } => write!(f, "node {}, {}: {}", name, field, message),
+            PatchError::Conflict { name, field, expected, found } => {
+                write!(f, "conflict in node {}, {}: expected {}, found {}", name, field, expected, found)
+            }
+            PatchError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PatchError::Toml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<toml::de::Error> for PatchError {
+    fn from(err: toml::de::Error) -> Self {
+        PatchError::Toml(err)
+    }
+}
+
+impl Fields {
+    pub fn is_empty(&self) -> bool {
+        *self == Fields::default()
+    }
+}
+
+fn type_text(node: &Node) -> String {
+    match node.node_type {
+        NodeType::INVALID => node.type_byte().to_string(),
+        node_type => node_type.to_string(),
+    }
+}
+
+fn role_text(role: u8) -> String {
+    match NodeRole::from(role) {
+        NodeRole::INVALID => role.to_string(),
+        known => known.to_string(),
+    }
+}
+
+//Type names set the type, numbers the raw byte.
+fn parse_type(value: &str) -> Result<(NodeType, u8), String> {
+    match value.trim().parse::<NodeType>() {
+        Ok(node_type) => Ok((node_type, u8::from(node_type))),
+        Err(err) => match value.trim().parse::<u8>() {
+            Ok(raw) => Ok((NodeType::from(raw), raw)),
+            Err(_) => Err(err),
+        },
+    }
+}
+
+fn parse_role(value: &str) -> Result<u8, String> {
+    match value.trim().parse::<NodeRole>() {
+        Ok(role) => Ok(role as u8),
+        Err(err) => match value.trim().parse::<u8>() {
+            Ok(role) if role <= 15 => Ok(role),
+            _ => Err(err),
+        },
+    }
+}
+
+fn invalid(name: &str, field: &str, message: String) -> PatchError {
+    PatchError::InvalidField { name: name.to_string(), field: field.to_string(), message }
+}
+
+fn check(node: &Node, expect: &Fields) -> Result<(), PatchError> {
+    let conflict = |field: &str, expected: String, found: String| PatchError::Conflict {
+        name: node.node_name.clone(),
+        field: field.to_string(),
+        expected,
+        found,
+    };
+
+    if let Some(cost) = expect.cost.filter(|cost| *cost != node.cp_cost) {
+        return Err(conflict("cost", cost.to_string(), node.cp_cost.to_string()));
+    }
+    if let Some(value) = expect.value.filter(|value| *value != node.node_value) {
+        return Err(conflict("value", value.to_string(), node.node_value.to_string()));
+    }
+    if let Some(node_type) = &expect.node_type {
+        let (expected, raw) =
+            parse_type(node_type).map_err(|message| invalid(&node.node_name, "expect.type", message))?;
+        let matches = match expected {
+            NodeType::INVALID => node.type_byte() == raw,
+            expected => node.node_type == expected,
+        };
+        if !matches {
+            return Err(conflict("type", node_type.clone(), type_text(node)));
+        }
+    }
+    if let Some(stage) = expect.stage.filter(|stage| *stage != node.stage) {
+        return Err(conflict("stage", stage.to_string(), node.stage.to_string()));
+    }
+    if let Some(role) = &expect.role {
+        let expected = parse_role(role).map_err(|message| invalid(&node.node_name, "expect.role", message))?;
+        if expected != node.role {
+            return Err(conflict("role", role.clone(), role_text(node.role)));
+        }
+    }
+    if let Some(ability) = expect.ability.as_ref().filter(|ability| **ability != node.ability) {
+        return Err(conflict("ability", ability.clone(), node.ability.clone()));
+    }
+    for (key, value) in expect.extra.iter() {
+        let found = node.extra.get(key);
+        if found != Some(value) {
+            return Err(conflict(key, value.to_string(), found.map(|v| v.to_string()).unwrap_or_default()));
+        }
+    }
+
+    Ok(())
+}
+
+fn set(node: &mut Node, fields: &Fields) -> Result<(), PatchError> {
+    let name = node.node_name.clone();
+
+    if let Some(cost) = fields.cost {
+        node.cp_cost = cost;
+    }
+    if let Some(value) = fields.value {
+        node.node_value = value;
+    }
+    if let Some(node_type) = &fields.node_type {
+        let (node_type, raw) = parse_type(node_type).map_err(|message| invalid(&name, "type", message))?;
+        node.node_type = node_type;
+        node.type_raw = raw;
+    }
+    if let Some(stage) = fields.stage {
+        if stage > 15 {
+            return Err(invalid(&name, "stage", format!("{} is out of range", stage)));
+        }
+        node.stage = stage;
+    }
+    if let Some(role) = &fields.role {
+        node.role = parse_role(role).map_err(|message| invalid(&name, "role", message))?;
+    }
+    if let Some(ability) = &fields.ability {
+        if ability.contains('\0') {
+            return Err(invalid(&name, "ability", "contains a null byte".to_string()));
+        }
+        node.ability = ability.clone();
+    }
+    //Only fields the layout of the file knows, anything else would be dropped on writing.
+    for (key, value) in fields.extra.iter() {
+        match node.extra.get_mut(key) {
+            Some(field) => *field = *value,
+            None => return Err(invalid(&name, key, "is not a field of this layout".to_string())),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn from_toml(text: &str) -> Result<Patch, PatchError> {
+    Ok(toml::from_str(text)?)
+}
+
+pub fn to_toml(patch: &Patch) -> String {
+    //Only strings and numbers, serializing can't fail.
+    toml::to_string(patch).unwrap()
+}
+
+//Applies every change or none, the crystal is only replaced once the whole patch went through.
+pub fn apply(patch: &Patch, crystarium: &Crystarium) -> Result<Crystarium, PatchError> {
+    if let Some(character) = patch.character.as_ref().filter(|character| **character != crystarium.character) {
+        return Err(PatchError::CharacterMismatch { expected: character.clone(), found: crystarium.character.clone() });
+    }
+
+    let mut patched = crystarium.clone();
+    for (position, node_patch) in patch.nodes.iter().enumerate() {
+        if patch.nodes[..position].iter().any(|earlier| earlier.name == node_patch.name) {
+            return Err(PatchError::DuplicateNode(node_patch.name.clone()));
+        }
+
+        let node = patched
+            .nodes
+            .iter_mut()
+            .find(|node| node.node_name == node_patch.name)
+            .ok_or_else(|| PatchError::UnknownNode(node_patch.name.clone()))?;
+
+        check(node, &node_patch.expect)?;
+        set(node, &node_patch.set)?;
+    }
+
+    Ok(patched)
+}
+
+//Patch which turns old into new, the old values are kept as expected values.
+pub fn from_diff(old: &Crystarium, new: &Crystarium) -> Result<Patch, PatchError> {
+    let result = diff(old, new);
+    if !result.added.is_empty() || !result.removed.is_empty() {
+        return Err(PatchError::Unsupported(format!(
+            "patches only change existing nodes, {} nodes are added and {} removed",
+            result.added.len(),
+            result.removed.len()
+        )));
+    }
+
+    let find =
+        |crystarium: &Crystarium, name: &str| crystarium.nodes.iter().find(|node| node.node_name == name).cloned();
+
+    //The patch is applied to the old file, so it has to expect the character of that one.
+    let mut patch = Patch { character: Some(old.character.clone()), nodes: Vec::new() };
+    for node_diff in result.changed.iter() {
+        let (Some(old_node), Some(new_node)) = (find(old, &node_diff.node_name), find(new, &node_diff.node_name)) else {
+            continue;
+        };
+
+        let mut node_patch = NodePatch { name: node_diff.node_name.clone(), ..Default::default() };
+        for change in node_diff.changes.iter() {
+            let (set, expect) = (&mut node_patch.set, &mut node_patch.expect);
+            match change.field.as_str() {
+                "cost" => (set.cost, expect.cost) = (Some(new_node.cp_cost), Some(old_node.cp_cost)),
+                "value" => (set.value, expect.value) = (Some(new_node.node_value), Some(old_node.node_value)),
+                "type" => (set.node_type, expect.node_type) = (Some(type_text(&new_node)), Some(type_text(&old_node))),
+                "stage" => (set.stage, expect.stage) = (Some(new_node.stage), Some(old_node.stage)),
+                "role" => (set.role, expect.role) = (Some(role_text(new_node.role)), Some(role_text(old_node.role))),
+                "ability" => {
+                    (set.ability, expect.ability) = (Some(new_node.ability.clone()), Some(old_node.ability.clone()))
+                }
+                key => match (old_node.extra.get(key), new_node.extra.get(key)) {
+                    (Some(old_value), Some(new_value)) => {
+                        set.extra.insert(key.to_string(), *new_value);
+                        expect.extra.insert(key.to_string(), *old_value);
+                    }
+                    _ => {
+                        return Err(PatchError::Unsupported(format!(
+                            "node {}: field {} only exists in one of the files",
+                            node_diff.node_name, key
+                        )))
+                    }
+                },
+            }
+        }
+
+        patch.nodes.push(node_patch);
+    }
+
+    Ok(patch)
+}
+
+#[test]
+fn test_patch() {
+    let node = |name: &str| Node {
+        node_name: name.to_string(),
+        cp_cost: 100,
+        node_value: 10,
+        stage: 1,
+        role: NodeRole::COMMANDO as u8,
+        ..Default::default()
+    };
+    let old = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![node("cr_lt0101000000"), node("cr_lt0102000000")],
+    };
+    let new = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            Node { cp_cost: 50, node_type: NodeType::STR, role: 9, ..node("cr_lt0101000000") },
+            node("cr_lt0102000000"),
+        ],
+    };
+
+    let patch = from_toml(&to_toml(&from_diff(&old, &new).unwrap())).unwrap();
+    assert_eq!(patch.nodes.len(), 1);
+    assert_eq!(patch.nodes[0].set.role.as_deref(), Some("9"));
+
+    let patched = apply(&patch, &old).unwrap();
+    assert!(diff(&patched, &new).is_empty());
+
+    //The patch was made for the old file, the new one already has other values.
+    match apply(&patch, &new) {
+        Err(PatchError::Conflict { field, .. }) => assert_eq!(field, "cost"),
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+
+    let unknown = from_toml("[[node]]\nname = \"cr_lt0109000000\"\nset = { cost = 1 }").unwrap();
+    assert!(matches!(apply(&unknown, &old), Err(PatchError::UnknownNode(_))));
+    assert!(from_toml("[[node]]\nname = \"cr_lt0101000000\"\nset = { cots = 1 }").is_err());
+}
+
+#[test]
+fn test_patch_from_other_character() {
+    let node = Node { node_name: "cr_lt0101000000".to_string(), cp_cost: 100, ..Default::default() };
+    let old = Crystarium { character: "Lightning".to_string(), nodes: vec![node.clone()] };
+    let new = Crystarium { character: "Fang".to_string(), nodes: vec![Node { cp_cost: 50, ..node }] };
+
+    //The patch goes onto the old file, so it expects the old character.
+    let patch = from_diff(&old, &new).unwrap();
+    assert_eq!(patch.character.as_deref(), Some("Lightning"));
+    assert_eq!(apply(&patch, &old).unwrap().nodes[0].cp_cost, 50);
+    assert!(matches!(apply(&patch, &new), Err(PatchError::CharacterMismatch { .. })));
+}
@@ -0,0 +1,212 @@
+use crate::crystal::{Crystarium, Node, NodeRole, NodeType};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+//Nodes trade their content (type, value and ability) with other nodes of the same group,
+//name, stage, role and CP cost stay where they are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Scope {
+    //Content moves between the stages of a role.
+    #[default]
+    Role,
+    //Content moves between the roles of a stage.
+    Stage,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Options {
+    //ChaCha8 is seeded with it, the same seed and options always give the same crystal.
+    pub seed: u64,
+    pub scope: Scope,
+    //Role level nodes are not shuffled.
+    pub keep_role_nodes: bool,
+    //Content only moves inside its stage, so the summed HP/STR/MAG of every stage stays the same.
+    pub keep_stage_budget: bool,
+    //ATB nodes get their values back in ascending order by stage, the gauge never shrinks.
+    pub keep_atb_order: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SpoilerEntry {
+    pub node_name: String,
+    pub stage: u8,
+    pub role: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Spoiler {
+    pub character: String,
+    pub options: Options,
+    //Only nodes whose content changed.
+    pub entries: Vec<SpoilerEntry>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Content {
+    node_type: NodeType,
+    type_raw: u8,
+    node_value: i16,
+    ability: String,
+}
+
+impl From<&Node> for Content {
+    fn from(node: &Node) -> Self {
+        Content {
+            node_type: node.node_type,
+            type_raw: node.type_raw,
+            node_value: node.node_value,
+            ability: node.ability.clone(),
+        }
+    }
+}
+
+impl Content {
+    fn apply(self, node: &mut Node) {
+        node.node_type = self.node_type;
+        node.type_raw = self.type_raw;
+        node.node_value = self.node_value;
+        node.ability = self.ability;
+    }
+}
+
+fn content_text(node: &Node) -> String {
+    match node.node_type {
+        NodeType::ABILITY => format!("{} {}", node.node_type, node.ability),
+        NodeType::HP | NodeType::STR | NodeType::MAG | NodeType::ATB => {
+            format!("+{} {}", node.node_value, node.node_type)
+        }
+        node_type => node_type.to_string(),
+    }
+}
+
+fn group(options: &Options, node: &Node) -> (u8, u8) {
+    match (options.scope, options.keep_stage_budget) {
+        (Scope::Role, false) => (node.role, 0),
+        (Scope::Role, true) => (node.role, node.stage),
+        (Scope::Stage, _) => (0, node.stage),
+    }
+}
+
+pub fn randomize(crystarium: &Crystarium, options: &Options) -> (Crystarium, Spoiler) {
+    let mut rng = ChaCha8Rng::seed_from_u64(options.seed);
+    let mut randomized = crystarium.clone();
+
+    //Groups in a fixed order, so the random numbers are drawn the same way on every run.
+    let mut groups: BTreeMap<(u8, u8), Vec<usize>> = BTreeMap::new();
+    for (index, node) in crystarium.nodes.iter().enumerate() {
+        if options.keep_role_nodes && node.node_type == NodeType::ROLE {
+            continue;
+        }
+        groups.entry(group(options, node)).or_default().push(index);
+    }
+
+    for indices in groups.values() {
+        let mut contents: Vec<Content> = indices.iter().map(|index| Content::from(&crystarium.nodes[*index])).collect();
+        contents.shuffle(&mut rng);
+
+        for (index, content) in indices.iter().zip(contents) {
+            content.apply(&mut randomized.nodes[*index]);
+        }
+    }
+
+    if options.keep_atb_order {
+        //Stable sort, nodes of the same stage keep the order of the file.
+        let mut atb: Vec<usize> = (0..randomized.nodes.len())
+            .filter(|index| randomized.nodes[*index].node_type == NodeType::ATB)
+            .collect();
+        atb.sort_by_key(|index| randomized.nodes[*index].stage);
+
+        let mut values: Vec<i16> = atb.iter().map(|index| randomized.nodes[*index].node_value).collect();
+        values.sort();
+        for (index, value) in atb.iter().zip(values) {
+            randomized.nodes[*index].node_value = value;
+        }
+    }
+
+    let entries = crystarium
+        .nodes
+        .iter()
+        .zip(randomized.nodes.iter())
+        .filter(|(old, new)| Content::from(*old) != Content::from(*new))
+        .map(|(old, new)| SpoilerEntry {
+            node_name: new.node_name.clone(),
+            stage: new.stage,
+            role: NodeRole::from(new.role).to_string(),
+            old: content_text(old),
+            new: content_text(new),
+        })
+        .collect();
+
+    let spoiler = Spoiler { character: crystarium.character.clone(), options: options.clone(), entries };
+    (randomized, spoiler)
+}
+
+impl fmt::Display for Spoiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} - seed {}, {:?} scope", self.character, self.options.seed, self.options.scope)?;
+        write!(f, "{} nodes changed", self.entries.len())?;
+
+        for entry in self.entries.iter() {
+            write!(
+                f,
+                "\n{:<16} {:>2} {:<10} {:<20} -> {}",
+                entry.node_name, entry.stage, entry.role, entry.old, entry.new
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_randomize() {
+    let node = |index: u8, stage: u8, node_type: NodeType, value: i16| Node {
+        node_name: format!("cr_lt01{:02}{:06}", stage, index),
+        stage,
+        role: NodeRole::COMMANDO as u8,
+        node_type,
+        node_value: value,
+        cp_cost: 10 * i32::from(index),
+        ..Default::default()
+    };
+    let mut nodes = Vec::new();
+    for stage in 1..=4 {
+        nodes.push(node(0, stage, NodeType::ROLE, 0));
+        nodes.push(node(1, stage, NodeType::HP, 20 * i16::from(stage)));
+        nodes.push(node(2, stage, NodeType::STR, 3 * i16::from(stage)));
+        nodes.push(node(3, stage, NodeType::ATB, i16::from(stage)));
+    }
+    let crystarium = Crystarium { character: "Lightning".to_string(), nodes };
+
+    let options = Options { seed: 7, keep_role_nodes: true, keep_atb_order: true, ..Default::default() };
+    let (first, spoiler) = randomize(&crystarium, &options);
+    let (second, _) = randomize(&crystarium, &options);
+    assert_eq!(crate::diff::diff(&first, &second).changed.len(), 0);
+    assert!(!spoiler.entries.is_empty());
+
+    //Role nodes stay, ATB values only grow.
+    assert!(first.nodes.iter().step_by(4).all(|node| node.node_type == NodeType::ROLE));
+    let atb: Vec<i16> =
+        first.nodes.iter().filter(|node| node.node_type == NodeType::ATB).map(|node| node.node_value).collect();
+    assert!(atb.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let options = Options { seed: 7, keep_stage_budget: true, ..Default::default() };
+    let (budget, _) = randomize(&crystarium, &options);
+    for stage in 1..=4 {
+        let sum = |crystarium: &Crystarium, node_type: NodeType| -> i64 {
+            crystarium
+                .nodes
+                .iter()
+                .filter(|node| node.stage == stage && node.node_type == node_type)
+                .map(|node| i64::from(node.node_value))
+                .sum()
+        };
+        assert_eq!(sum(&budget, NodeType::HP), sum(&crystarium, NodeType::HP));
+        assert_eq!(sum(&budget, NodeType::STR), sum(&crystarium, NodeType::STR));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::crystal::{Crystarium, Node, NodeType};
+use serde::Serialize;
+
+//Nodes a transform applies to, an empty list matches everything.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Filter {
+    pub stages: Vec<u8>,
+    pub roles: Vec<u8>,
+    pub types: Vec<NodeType>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Transform {
+    pub filter: Filter,
+    //CP cost multiplier, rounded to the nearest integer.
+    pub cost_factor: Option<f64>,
+    //Multiplier and upper limit of the value, only HP/STR/MAG nodes have stat values.
+    pub value_factor: Option<f64>,
+    pub value_cap: Option<i16>,
+}
+
+impl Filter {
+    pub fn matches(&self, node: &Node) -> bool {
+        (self.stages.is_empty() || self.stages.contains(&node.stage))
+            && (self.roles.is_empty() || self.roles.contains(&node.role))
+            && (self.types.is_empty() || self.types.contains(&node.node_type))
+    }
+}
+
+impl Transform {
+    pub fn check(&self) -> Result<(), String> {
+        for (name, factor) in [("cost factor", self.cost_factor), ("value factor", self.value_factor)] {
+            if let Some(factor) = factor.filter(|factor| !factor.is_finite() || *factor < 0.0) {
+                return Err(format!("{} {} has to be a non-negative number", name, factor));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, node: &mut Node) {
+        if !self.filter.matches(node) {
+            return;
+        }
+
+        //Casts saturate, a factor can't wrap a cost or value around.
+        if let Some(factor) = self.cost_factor {
+            node.cp_cost = (f64::from(node.cp_cost) * factor).round() as i32;
+        }
+
+        if matches!(node.node_type, NodeType::HP | NodeType::STR | NodeType::MAG) {
+            if let Some(factor) = self.value_factor {
+                node.node_value = (f64::from(node.node_value) * factor).round() as i16;
+            }
+            if let Some(cap) = self.value_cap {
+                node.node_value = node.node_value.min(cap);
+            }
+        }
+    }
+}
+
+//Transforms run one after another, a later one sees the result of the earlier ones.
+pub fn rebalance(crystarium: &Crystarium, transforms: &[Transform]) -> Result<Crystarium, String> {
+    for transform in transforms.iter() {
+        transform.check()?;
+    }
+
+    let mut rebalanced = crystarium.clone();
+    for transform in transforms.iter() {
+        for node in rebalanced.nodes.iter_mut() {
+            transform.apply(node);
+        }
+    }
+
+    Ok(rebalanced)
+}
+
+#[test]
+fn test_rebalance() {
+    use crate::crystal::NodeRole;
+
+    let node = |stage: u8, role: NodeRole, node_type: NodeType, value: i16, cp_cost: i32| Node {
+        stage,
+        role: role as u8,
+        node_type,
+        node_value: value,
+        cp_cost,
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node(1, NodeRole::COMMANDO, NodeType::HP, 40, 101),
+            node(2, NodeRole::COMMANDO, NodeType::STR, 8, 300),
+            node(2, NodeRole::MEDIC, NodeType::ATB, 1, 500),
+        ],
+    };
+
+    let halve = Transform { cost_factor: Some(0.5), ..Default::default() };
+    let stage_two = Transform {
+        filter: Filter { stages: vec![2], ..Default::default() },
+        value_factor: Some(3.0),
+        value_cap: Some(20),
+        ..Default::default()
+    };
+
+    let rebalanced = rebalance(&crystarium, &[halve, stage_two]).unwrap();
+    let costs: Vec<i32> = rebalanced.nodes.iter().map(|node| node.cp_cost).collect();
+    let values: Vec<i16> = rebalanced.nodes.iter().map(|node| node.node_value).collect();
+    assert_eq!(costs, vec![51, 150, 250]);
+    assert_eq!(values, vec![40, 20, 1]);
+
+    let medic_hp = Filter { roles: vec![NodeRole::MEDIC as u8], types: vec![NodeType::HP], ..Default::default() };
+    assert!(!medic_hp.matches(&crystarium.nodes[0]));
+
+    //Zero makes every node free, anything below is rejected.
+    let factor = |cost_factor: f64| Transform { cost_factor: Some(cost_factor), ..Default::default() };
+    assert!(rebalance(&crystarium, &[factor(0.0)]).unwrap().nodes.iter().all(|node| node.cp_cost == 0));
+    assert!(rebalance(&crystarium, &[factor(-0.001)]).is_err());
+    assert!(rebalance(&crystarium, &[factor(-1.0)]).is_err());
+}
@@ -0,0 +1,206 @@
+use crate::crystal::Node;
+use crate::crystal_page::CrystalPage;
+use crate::view::{ConvertVecNode, CrystalData, NodeFragment};
+use actix_web::{cookie::Cookie, HttpRequest};
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+pub const SESSION_COOKIE: &str = "crystal_session";
+
+//Sessions without any request for this long are dropped.
+pub const SESSION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+pub struct Session {
+    //Uploaded crystals keyed by character, the pages are cached per character as well.
+    pub workspace: BTreeMap<String, CrystalData>,
+    pages: BTreeMap<String, Vec<CrystalPage>>,
+    last_access: Instant,
+}
+
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+    timeout: Duration,
+    counter: AtomicU64,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            workspace: BTreeMap::new(),
+            pages: BTreeMap::new(),
+            last_access: Instant::now(),
+        }
+    }
+}
+
+impl Session {
+    pub fn add(&mut self, crystal_data: CrystalData) {
+        //A new upload of the same character replaces the old one.
+        let character = crystal_data.character();
+        self.pages.remove(&character);
+        self.workspace.insert(character, crystal_data);
+    }
+
+    pub fn characters(&self) -> Vec<String> {
+        self.workspace.keys().cloned().collect()
+    }
+
+    pub fn pages(&mut self, character: &str) -> Option<&Vec<CrystalPage>> {
+        let crystal_data = self.workspace.get(character)?;
+
+        //Pages are built on first view and kept until the nodes change.
+        let pages = self.pages.entry(character.to_string()).or_insert_with(|| {
+            let mut nodes: Vec<NodeFragment> = crystal_data.crystal_data.nodes.clone().convert();
+            CrystalPage::convert(&crystal_data.crystal_data.character, &mut nodes)
+        });
+
+        Some(pages)
+    }
+
+    pub fn invalidate_pages(&mut self, character: &str) {
+        self.pages.remove(character);
+    }
+
+    pub fn find_node(&self, name: &str) -> Option<&Node> {
+        //Node names carry the character code, so they are unique across the workspace.
+        self.workspace
+            .values()
+            .flat_map(|crystal_data| crystal_data.crystal_data.nodes.iter())
+            .find(|node| node.node_name == name)
+    }
+
+    pub fn find_node_mut(&mut self, name: &str) -> Option<(String, &mut Node)> {
+        self.workspace.iter_mut().find_map(|(character, crystal_data)| {
+            crystal_data
+                .crystal_data
+                .nodes
+                .iter_mut()
+                .find(|node| node.node_name == name)
+                .map(|node| (character.clone(), node))
+        })
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        SessionStore::new(SESSION_TIMEOUT)
+    }
+}
+
+impl SessionStore {
+    pub fn new(timeout: Duration) -> Self {
+        SessionStore {
+            sessions: Mutex::new(HashMap::new()),
+            timeout,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn new_id(&self) -> String {
+        //Two randomly keyed hashes over time and a counter, good enough to keep LAN users apart.
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut id = String::new();
+        for _ in 0..2 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(nanos);
+            hasher.write_u64(count);
+            id.push_str(&format!("{:016x}", hasher.finish()));
+        }
+
+        id
+    }
+
+    //The time is passed in, so every request evicts and touches sessions against the same instant.
+    fn evict_idle(&self, sessions: &mut HashMap<String, Session>, now: Instant) {
+        sessions.retain(|_, session| now.saturating_duration_since(session.last_access) < self.timeout);
+    }
+
+    pub fn upload(&self, id: Option<&str>, uploads: Vec<CrystalData>) -> String {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        self.evict_idle(&mut sessions, now);
+
+        //Keep the id of a known session, everything else gets a fresh one.
+        let id = match id {
+            Some(id) if sessions.contains_key(id) => id.to_string(),
+            _ => self.new_id(),
+        };
+
+        let session = sessions.entry(id.clone()).or_default();
+        session.last_access = now;
+        for crystal_data in uploads {
+            session.add(crystal_data);
+        }
+
+        id
+    }
+
+    pub fn with_session<R>(&self, id: Option<&str>, f: impl FnOnce(&mut Session) -> R) -> Option<R> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        self.evict_idle(&mut sessions, now);
+
+        let session = sessions.get_mut(id?)?;
+        session.last_access = now;
+
+        Some(f(session))
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn session_id(req: &HttpRequest) -> Option<String> {
+    req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+pub fn session_cookie(id: &str) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, id.to_string())
+        .path("/")
+        .http_only(true)
+        .finish()
+}
+
+#[test]
+fn test_session_eviction() {
+    let store = SessionStore::new(Duration::from_secs(60));
+    let first = store.upload(None, vec![CrystalData::default()]);
+    let second = store.upload(None, vec![CrystalData::default()]);
+
+    assert_ne!(first, second);
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.upload(Some(&first), Vec::new()), first);
+    assert!(store.with_session(Some(&first), |_| ()).is_some());
+
+    //Evict at fixed points in time instead of sleeping through a real timeout.
+    let mut sessions = store.sessions.lock().unwrap();
+    let start = Instant::now();
+    sessions.values_mut().for_each(|session| session.last_access = start);
+    sessions.get_mut(&second).unwrap().last_access = start + Duration::from_secs(30);
+
+    store.evict_idle(&mut sessions, start + Duration::from_secs(59));
+    assert_eq!(sessions.len(), 2);
+    store.evict_idle(&mut sessions, start + Duration::from_secs(60));
+    assert_eq!(sessions.keys().collect::<Vec<_>>(), vec![&second]);
+    store.evict_idle(&mut sessions, start + Duration::from_secs(90));
+    assert!(sessions.is_empty());
+}
@@ -0,0 +1,143 @@
+use crate::crystal::{Entry, WdbError};
+use byteorder::{BigEndian, ByteOrder};
+use serde::Serialize;
+use std::{fmt, io::Cursor};
+
+// Generic WDB table
+//     • every record is a row of 4 byte fields
+//     • !!strtypelist and !!typelist hold type codes of the fields
+// Which code stands for which field type isn't documented anywhere and hasn't been checked against real tables, so
+// files with type codes are rejected with WdbError::UnknownFieldType instead of guessing a layout. Records of files
+// without type lists are shown as raw words.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Value {
+    Word(u32),
+    //Bytes of a record which don't fill a whole field.
+    Bytes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Row {
+    pub name: String,
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Table {
+    pub rows: Vec<Row>,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Word(value) => write!(f, "0x{:08x}", value),
+            Value::Bytes(bytes) => {
+                for byte in bytes.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn section<'a>(data: &'a [u8], entry: &Entry) -> Result<&'a [u8], WdbError> {
+    let start = entry.offset() as usize;
+    data.get(start..start + entry.length() as usize)
+        .ok_or_else(|| WdbError::EntryOutOfRange(entry.name().to_string()))
+}
+
+fn check_field_types(section: &'static str, list: &[u8]) -> Result<(), WdbError> {
+    //No code is known yet, an empty list is the only one which doesn't need guessing.
+    match list.chunks(4).next() {
+        Some(code) => {
+            let code = code.iter().fold(0, |code, byte| code << 8 | u32::from(*byte));
+            Err(WdbError::UnknownFieldType { section, code })
+        }
+        None => Ok(()),
+    }
+}
+
+fn decode_row(name: &str, record: &[u8]) -> Row {
+    let values = record
+        .chunks(4)
+        .map(|word| match word.len() {
+            4 => Value::Word(BigEndian::read_u32(word)),
+            _ => Value::Bytes(word.to_vec()),
+        })
+        .collect();
+
+    Row { name: name.to_string(), values }
+}
+
+pub fn read_table(data: &[u8]) -> Result<Table, WdbError> {
+    if data.len() < Entry::HEADERSIZE {
+        return Err(WdbError::TruncatedHeader);
+    }
+
+    let magic = String::from_utf8_lossy(&data[..4]).to_string();
+    if magic.trim_end_matches('\0') != "WPD" {
+        return Err(WdbError::BadMagic(magic));
+    }
+
+    //Sections are looked up by offset, unlike the crystal loader the order of the file doesn't matter.
+    let mut reader = Cursor::new(data);
+    reader.set_position(Entry::HEADERSIZE as u64);
+    let entries = Entry::load_entries(&mut reader, BigEndian::read_i32(&data[4..8]))?;
+
+    for name in ["!!strtypelist", "!!typelist"] {
+        if let Some(entry) = entries.iter().find(|entry| entry.name() == name) {
+            check_field_types(name, section(data, entry)?)?;
+        }
+    }
+
+    let mut table = Table::default();
+    for entry in entries.iter().filter(|entry| !entry.name().starts_with('!')) {
+        table.rows.push(decode_row(entry.name(), section(data, entry)?));
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+fn test_wdb(sections: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut data = b"WPD\0".to_vec();
+    data.extend_from_slice(&(sections.len() as i32).to_be_bytes());
+    data.extend_from_slice(&[0; 8]);
+    let mut offset = 16 + 32 * sections.len();
+    for (name, bytes) in sections.iter() {
+        let mut name_field = name.as_bytes().to_vec();
+        name_field.resize(16, 0);
+        data.extend_from_slice(&name_field);
+        data.extend_from_slice(&(offset as i32).to_be_bytes());
+        data.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        offset += bytes.len();
+    }
+    for (_, bytes) in sections.iter() {
+        data.extend_from_slice(bytes);
+    }
+
+    data
+}
+
+#[test]
+fn test_read_table() {
+    let record = [&(-5i32).to_be_bytes()[..], &1u32.to_be_bytes(), &[0x12, 0x34, 0, 1, 0xff]].concat();
+    let data = test_wdb(&[("!!string", b"\0it_potion\0".to_vec()), ("!!strtypelist", Vec::new()), ("it_potion", record)]);
+
+    let table = read_table(&data).unwrap();
+    assert_eq!(
+        table.rows[0].values,
+        vec![Value::Word(0xfffffffb), Value::Word(1), Value::Word(0x12340001), Value::Bytes(vec![0xff])]
+    );
+}
+
+#[test]
+fn test_unknown_field_type() {
+    //Type codes are not guessed, whatever their length.
+    for (section, list) in [("!!strtypelist", vec![0, 0, 0, 3]), ("!!typelist", vec![0, 1, 0, 0, 0])] {
+        let data = test_wdb(&[(section, list), ("it_potion", vec![0; 8])]);
+        assert!(matches!(read_table(&data), Err(WdbError::UnknownFieldType { section: name, .. }) if name == section));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::crystal::{Crystarium, Entry, FileStructure, Layout, Node, NodeRole, NodeType};
+use serde::Serialize;
+use std::fmt;
+
+//The game has ten crystarium stages.
+pub const MAX_STAGE: u8 = 10;
+
+// Positions of the fields inside a node record
+//     • 0: int CP cost
+//     • 10: byte node type
+//     • 11: byte stage * 16 + role
+const COST_FIELD: usize = 0;
+const TYPE_FIELD: usize = 10;
+const STAGE_ROLE_FIELD: usize = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Reason {
+    UnknownType,
+    UnknownRole,
+    StageOutOfRange,
+    NegativeCost,
+    NameMismatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub node_name: String,
+    //Absolute position of the field in the file, None for nodes which are not part of the file yet.
+    pub offset: Option<usize>,
+    pub raw: i64,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::UnknownType => write!(f, "unknown node type"),
+            Reason::UnknownRole => write!(f, "unknown role"),
+            Reason::StageOutOfRange => write!(f, "stage outside of 1..={}", MAX_STAGE),
+            Reason::NegativeCost => write!(f, "negative CP cost"),
+            Reason::NameMismatch => write!(f, "name doesn't match cr_<char><role><stage>..."),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{} @0x{:x}: {}", self.node_name, offset, self.reason)?,
+            None => write!(f, "{}: {}", self.node_name, self.reason)?,
+        }
+
+        //Bytes are shown like in a hex editor, the cost as number.
+        match self.reason {
+            Reason::UnknownType | Reason::UnknownRole | Reason::StageOutOfRange => write!(f, " (0x{:02x})", self.raw),
+            Reason::NegativeCost => write!(f, " ({})", self.raw),
+            Reason::NameMismatch => Ok(()),
+        }
+    }
+}
+
+fn name_matches(layout: &dyn Layout, node: &Node, character: Option<&str>) -> bool {
+    //cr_ + character code + role, stage and index digits, e.g. cr_lt0102000000 for a commando node of stage 2.
+    let name = node.node_name.as_str();
+    let Some(rest) = name.get(5..) else {
+        return false;
+    };
+    let digits = |position: usize| rest.get(position..position + 2).and_then(|digits| digits.parse::<u8>().ok());
+
+    layout.character(name).is_some()
+        && character.is_none_or(|character| layout.character(name) == Some(character))
+        && rest.len() == 10
+        && rest.bytes().all(|b| b.is_ascii_alphanumeric())
+        && digits(0) == Some(node.role)
+        && digits(2) == Some(node.stage)
+}
+
+//Checks which need nothing but the node itself, used by the viewer to mark nodes.
+pub fn check(node: &Node) -> Vec<Reason> {
+    let mut reasons = Vec::new();
+
+    if matches!(node.node_type, NodeType::INVALID) {
+        reasons.push(Reason::UnknownType);
+    }
+    if matches!(NodeRole::from(node.role), NodeRole::INVALID) {
+        reasons.push(Reason::UnknownRole);
+    }
+    if !(1..=MAX_STAGE).contains(&node.stage) {
+        reasons.push(Reason::StageOutOfRange);
+    }
+    if node.cp_cost < 0 {
+        reasons.push(Reason::NegativeCost);
+    }
+
+    reasons
+}
+
+fn check_node(fstruct: &FileStructure, node: &Node, character: Option<&str>, issues: &mut Vec<Issue>) {
+    //Offsets come from the record the node was read from, names from its entry in the table.
+    let position = fstruct.entries().iter().position(|e| !e.name().starts_with('!') && e.name() == node.node_name);
+    let entry = position.map(|position| &fstruct.entries()[position]);
+    let offset = |field: usize| entry.map(|e| e.offset() as usize + field);
+
+    let stage_role = match node.stage <= 15 && node.role <= 15 {
+        true => node.stage_role_byte().into(),
+        false => i64::from(node.stage) * 16 + i64::from(node.role),
+    };
+    for reason in check(node) {
+        let (offset, raw) = match reason {
+            Reason::UnknownType => (offset(TYPE_FIELD), node.type_byte().into()),
+            Reason::UnknownRole | Reason::StageOutOfRange => (offset(STAGE_ROLE_FIELD), stage_role),
+            _ => (offset(COST_FIELD), node.cp_cost.into()),
+        };
+        issues.push(Issue { node_name: node.node_name.clone(), offset, raw, reason });
+    }
+
+    if !name_matches(fstruct.layout(), node, character) {
+        let name_offset = position.map(|position| Entry::HEADERSIZE + Entry::ENTRYSIZE * position);
+        issues.push(Issue { node_name: node.node_name.clone(), offset: name_offset, raw: 0, reason: Reason::NameMismatch });
+    }
+}
+
+pub fn validate(fstruct: &FileStructure, crystarium: &Crystarium) -> Vec<Issue> {
+    //All nodes of a file belong to the character of the first one.
+    let layout = fstruct.layout();
+    let character = crystarium.nodes.first().and_then(|node| layout.character(&node.node_name));
+
+    let mut issues = Vec::new();
+    for node in crystarium.nodes.iter() {
+        check_node(fstruct, node, character, &mut issues);
+    }
+
+    issues
+}
+
+#[test]
+fn test_validate() {
+    let node = Node {
+        node_name: "cr_lt0101000000".to_string(),
+        node_type: NodeType::HP,
+        stage: 1,
+        role: 1,
+        ..Default::default()
+    };
+    let crystarium = Crystarium {
+        character: "Lightning".to_string(),
+        nodes: vec![
+            node.clone(),
+            Node { node_name: "cr_lt0101000002".to_string(), node_type: NodeType::INVALID, ..node.clone() },
+            Node { node_name: "cr_lt0711000003".to_string(), role: 7, stage: 11, ..node.clone() },
+            Node { node_name: "cr_lt0101000004".to_string(), cp_cost: -5, ..node.clone() },
+            Node { node_name: "cr_fa0101000005".to_string(), ..node.clone() },
+            //Named as stage 3 of the ravager, but stored as stage 7 of the commando.
+            Node { node_name: "cr_lt0203000006".to_string(), stage: 7, ..node.clone() },
+            Node { node_name: "cr_lt0107000007".to_string(), stage: 7, ..node.clone() },
+        ],
+    };
+
+    let reasons: Vec<(String, Reason)> = validate(&FileStructure::default(), &crystarium)
+        .into_iter()
+        .map(|issue| (issue.node_name, issue.reason))
+        .collect();
+
+    assert_eq!(
+        reasons,
+        vec![
+            ("cr_lt0101000002".to_string(), Reason::UnknownType),
+            ("cr_lt0711000003".to_string(), Reason::UnknownRole),
+            ("cr_lt0711000003".to_string(), Reason::StageOutOfRange),
+            ("cr_lt0101000004".to_string(), Reason::NegativeCost),
+            ("cr_fa0101000005".to_string(), Reason::NameMismatch),
+            ("cr_lt0203000006".to_string(), Reason::NameMismatch),
+        ]
+    );
+}
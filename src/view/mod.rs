@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+use crate::analysis::{Analysis, RoleStats, StageStats};
 use crate::crystal::*;
+use crate::diff::{diff, Diff};
+use crate::rebalance::{Filter, Transform};
+use crate::validation;
 use actix_multipart::form::{tempfile::TempFile, MultipartForm};
 use askama::Template;
+use serde::Deserialize;
 
 #[derive(Template)]
 #[template(path = "index.html")]
@@ -12,10 +17,118 @@ pub struct Index;
 #[template(path = "node_edit.html")]
 pub struct NodeViewer {
     pub character: String,
+    pub characters: Vec<String>,
     pub current_page: i16,
     pub next_page: i16,
     pub prev_page: i16,
     pub roles: Vec<RoleFragment>,
+    pub issues: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "node.html")]
+pub struct NodeTemplate {
+    pub node: NodeFragment,
+}
+
+#[derive(Template)]
+#[template(path = "node_form.html")]
+pub struct NodeFormTemplate {
+    pub node: NodeFragment,
+    pub types: Vec<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "overview.html")]
+pub struct OverviewTemplate {
+    pub character: String,
+    pub characters: Vec<String>,
+    pub stages: Vec<u8>,
+    pub rows: Vec<OverviewRow>,
+    pub totals: Vec<OverviewCell>,
+}
+
+#[derive(Template)]
+#[template(path = "analysis.html")]
+pub struct AnalysisTemplate {
+    pub character: String,
+    pub characters: Vec<String>,
+    pub analysis: Analysis,
+    pub charts: Vec<RoleChart>,
+}
+
+#[derive(Template)]
+#[template(path = "chart.html")]
+pub struct ChartTemplate {
+    pub charts: Vec<RoleChart>,
+}
+
+#[derive(Template)]
+#[template(path = "diff.html")]
+pub struct DiffPage;
+
+#[derive(Template)]
+#[template(path = "rebalance.html")]
+pub struct RebalanceTemplate {
+    pub character: String,
+    pub characters: Vec<String>,
+    pub stages: Vec<u8>,
+    pub roles: Vec<String>,
+    pub types: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "diff_result.html")]
+pub struct DiffTemplate {
+    pub old_name: String,
+    pub new_name: String,
+    pub diff: Diff,
+    pub rows: Vec<DiffRow>,
+}
+
+//One node of both files side by side, None on the side which doesn't have it.
+#[derive(Clone, Debug)]
+pub struct DiffRow {
+    pub name: String,
+    //changed, added or removed, used as class of the cells.
+    pub status: String,
+    //Old and new file.
+    pub cells: [Option<DiffCell>; 2],
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffCell {
+    pub node: NodeFragment,
+    pub changed: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RoleChart {
+    pub role: String,
+    pub max_cp: i64,
+    //HP on the left axis, STR and MAG share the right one.
+    pub max_hp: i64,
+    pub max_stat: i64,
+    pub lines: Vec<ChartLine>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ChartLine {
+    pub name: String,
+    pub points: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OverviewRow {
+    pub role: String,
+    pub cells: Vec<OverviewCell>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OverviewCell {
+    pub count: usize,
+    pub cp: i64,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -28,11 +141,14 @@ pub struct RoleFragment {
 pub struct NodeFragment {
     pub name: String,
     pub cost: i32,
+    pub ability: String,
     pub value: i16,
     pub role: String,
     pub stage: i16,
     pub r#type: String,
     pub image: String,
+    //Reasons of the validation, empty for a plausible node.
+    pub issues: String,
 }
 
 #[derive(Debug, MultipartForm)]
@@ -41,9 +157,68 @@ pub struct UploadForm {
     pub files: Vec<TempFile>,
 }
 
+#[derive(Debug, MultipartForm)]
+pub struct DiffForm {
+    pub old: TempFile,
+    pub new: TempFile,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NodeForm {
+    pub cp_cost: i32,
+    pub node_value: i16,
+    pub node_type: String,
+    pub stage: u8,
+    pub role: String,
+}
+
+//Empty fields mean all nodes or no change.
+#[derive(Debug, Deserialize)]
+pub struct RebalanceForm {
+    pub stage: String,
+    pub role: String,
+    pub node_type: String,
+    pub cost_factor: String,
+    pub value_factor: String,
+    pub value_cap: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub character: Option<String>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct CrystalData {
     pub crystal_data: Crystarium,
+    pub file_structure: FileStructure,
+    pub file_name: String,
+}
+
+impl CrystalData {
+    pub fn character(&self) -> String {
+        //Unknown crystals are kept apart by their file name.
+        match self.crystal_data.character.as_str() {
+            "" | "None" => self.download_name(),
+            character => character.to_string(),
+        }
+    }
+
+    pub fn download_name(&self) -> String {
+        //Prefer the uploaded name, else build it from the character code of the node names (cr_lt... -> crystal_lt.wdb).
+        if !self.file_name.is_empty() {
+            return self.file_name.clone();
+        }
+
+        let code = self
+            .crystal_data
+            .nodes
+            .first()
+            .and_then(|node| node.node_name.get(3..5))
+            .unwrap_or("none");
+
+        format!("crystal_{}.wdb", code)
+    }
 }
 
 pub trait ConvertVecNode {
@@ -58,18 +233,258 @@ impl ConvertVecNode for Vec<Node> {
 
 impl From<Node> for NodeFragment {
     fn from(value: Node) -> Self {
+        let r#type = value.type_label();
+        let role = value.role_label();
+        let issues = validation::check(&value).iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ");
+
         NodeFragment {
             name: value.node_name,
             cost: value.cp_cost,
+            ability: value.ability,
             value: value.node_value,
             stage: value.stage.into(),
-            role: NodeRole::from(value.role).to_string(),
-            r#type: value.node_type.to_string(),
+            role,
+            r#type,
             image: value.node_type.to_imagesrc().to_string(),
+            issues,
         }
     }
 }
 
+impl NodeForm {
+    pub fn apply(&self, node: &mut Node) -> Result<(), String> {
+        //Validate everything before touching the node, so a bad form leaves it unchanged.
+        //Type, role and stage are only written when they were changed, unknown values survive an edit of the cost.
+        let node_type = match self.node_type == node.type_label() {
+            true => None,
+            false => Some(self.node_type.parse::<NodeType>()?),
+        };
+        let role = match self.role == node.role_label() {
+            true => None,
+            false => Some(self.role.parse::<NodeRole>()? as u8),
+        };
+        if self.stage != node.stage && self.stage > 15 {
+            return Err(format!("stage {} does not fit into the record", self.stage));
+        }
+
+        node.cp_cost = self.cp_cost;
+        node.node_value = self.node_value;
+        node.stage = self.stage;
+        if let Some(node_type) = node_type {
+            node.node_type = node_type;
+        }
+        if let Some(role) = role {
+            node.role = role;
+        }
+
+        Ok(())
+    }
+}
+
+fn optional<T: std::str::FromStr>(field: &str, value: &str) -> Result<Option<T>, String> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value.parse::<T>().map(Some).map_err(|_| format!("{} {:?} is not a number", field, value)),
+    }
+}
+
+impl RebalanceForm {
+    pub fn transform(&self) -> Result<Transform, String> {
+        let mut filter = Filter::default();
+        if let Some(stage) = optional::<u8>("stage", &self.stage)? {
+            filter.stages.push(stage);
+        }
+        if !self.role.is_empty() {
+            filter.roles.push(self.role.parse::<NodeRole>()? as u8);
+        }
+        if !self.node_type.is_empty() {
+            filter.types.push(self.node_type.parse::<NodeType>()?);
+        }
+
+        let transform = Transform {
+            filter,
+            cost_factor: optional("cost factor", &self.cost_factor)?,
+            value_factor: optional("value factor", &self.value_factor)?,
+            value_cap: optional("value cap", &self.value_cap)?,
+        };
+        transform.check()?;
+
+        Ok(transform)
+    }
+}
+
+impl RebalanceTemplate {
+    pub fn new(character: String, characters: Vec<String>, crystarium: &Crystarium) -> Self {
+        RebalanceTemplate {
+            character,
+            characters,
+            stages: crystarium.nodes.iter().map(|n| n.stage).collect::<BTreeSet<_>>().into_iter().collect(),
+            roles: NodeRole::ALL.iter().map(|r| r.to_string()).collect(),
+            types: NodeType::ALL.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+}
+
+impl NodeFormTemplate {
+    pub fn new(node: NodeFragment) -> Self {
+        let mut types: Vec<String> = NodeType::ALL.iter().map(|t| t.to_string()).collect();
+        let mut roles: Vec<String> = NodeRole::ALL.iter().map(|r| r.to_string()).collect();
+
+        //Unknown values are offered as they are, so saving the form doesn't replace them with the first option.
+        if !types.contains(&node.r#type) {
+            types.insert(0, node.r#type.clone());
+        }
+        if !roles.contains(&node.role) {
+            roles.insert(0, node.role.clone());
+        }
+
+        NodeFormTemplate { node, types, roles }
+    }
+}
+
+impl RoleChart {
+    pub const WIDTH: i64 = 480;
+    pub const HEIGHT: i64 = 240;
+
+    pub fn width(&self) -> i64 {
+        Self::WIDTH
+    }
+
+    pub fn height(&self) -> i64 {
+        Self::HEIGHT
+    }
+
+    fn line(name: &str, points: &[(i64, i64)], max_x: i64, max_y: i64) -> ChartLine {
+        //SVG y grows downwards, so the value is subtracted from the height.
+        let points = points
+            .iter()
+            .map(|(x, y)| {
+                format!(
+                    "{},{}",
+                    x * Self::WIDTH / max_x.max(1),
+                    Self::HEIGHT - y * Self::HEIGHT / max_y.max(1)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        ChartLine { name: name.to_string(), points }
+    }
+}
+
+impl From<&RoleStats> for RoleChart {
+    fn from(stats: &RoleStats) -> Self {
+        //Every line starts at zero CP and steps to the cumulative values of each stage.
+        let series = |value: fn(&StageStats) -> i64| -> Vec<(i64, i64)> {
+            let mut points = vec![(0, 0)];
+            points.extend(stats.stages.iter().map(|stage| (stage.cumulative_cp, value(stage))));
+            points
+        };
+
+        let max_cp = stats.total_cp;
+        let max_hp = stats.hp;
+        let max_stat = stats.strength.max(stats.magic);
+
+        RoleChart {
+            role: stats.role.clone(),
+            max_cp,
+            max_hp,
+            max_stat,
+            lines: vec![
+                Self::line("hp", &series(|stage| stage.cumulative_hp), max_cp, max_hp),
+                Self::line("str", &series(|stage| stage.cumulative_strength), max_cp, max_stat),
+                Self::line("mag", &series(|stage| stage.cumulative_magic), max_cp, max_stat),
+            ],
+        }
+    }
+}
+
+pub fn charts(analysis: &Analysis) -> Vec<RoleChart> {
+    analysis.roles.iter().map(RoleChart::from).collect()
+}
+
+impl OverviewCell {
+    fn add(&mut self, cp_cost: i32) {
+        self.count += 1;
+        self.cp += i64::from(cp_cost);
+    }
+}
+
+impl OverviewTemplate {
+    pub fn new(character: String, characters: Vec<String>, crystarium: &Crystarium) -> Self {
+        //Every stage with nodes becomes a column, every role a row.
+        let stages: Vec<u8> = crystarium.nodes.iter().map(|n| n.stage).collect::<BTreeSet<_>>().into_iter().collect();
+        let column = |stage: u8| stages.iter().position(|s| *s == stage).unwrap_or_default();
+
+        let mut roles: BTreeMap<u8, Vec<OverviewCell>> = BTreeMap::new();
+        let mut totals = vec![OverviewCell::default(); stages.len()];
+        for node in crystarium.nodes.iter() {
+            let cells = roles.entry(node.role).or_insert_with(|| vec![OverviewCell::default(); stages.len()]);
+            cells[column(node.stage)].add(node.cp_cost);
+            totals[column(node.stage)].add(node.cp_cost);
+        }
+
+        let rows = roles
+            .into_iter()
+            .map(|(role, cells)| OverviewRow {
+                role: NodeRole::from(role).to_string(),
+                cells,
+            })
+            .collect();
+
+        OverviewTemplate {
+            character,
+            characters,
+            stages,
+            rows,
+            totals,
+        }
+    }
+}
+
+impl DiffCell {
+    pub fn is_changed(&self, field: &str) -> bool {
+        self.changed.iter().any(|changed| changed == field)
+    }
+}
+
+impl DiffTemplate {
+    pub fn new(old_name: String, new_name: String, old: &Crystarium, new: &Crystarium) -> Self {
+        let diff = diff(old, new);
+        let cell = |crystarium: &Crystarium, name: &str, changed: &[String]| {
+            crystarium.nodes.iter().find(|node| node.node_name == name).map(|node| DiffCell {
+                node: NodeFragment::from(node.clone()),
+                changed: changed.to_vec(),
+            })
+        };
+
+        //Changed and removed nodes in the order of the old file, added ones at the end.
+        let mut rows = Vec::new();
+        for node in old.nodes.iter() {
+            let name = node.node_name.as_str();
+            if let Some(node_diff) = diff.changed.iter().find(|node_diff| node_diff.node_name == name) {
+                let changed: Vec<String> = node_diff.changes.iter().map(|change| change.field.clone()).collect();
+                rows.push(DiffRow {
+                    name: name.to_string(),
+                    status: "changed".to_string(),
+                    cells: [cell(old, name, &changed), cell(new, name, &changed)],
+                });
+            } else if diff.removed.iter().any(|removed| removed == name) {
+                rows.push(DiffRow {
+                    name: name.to_string(),
+                    status: "removed".to_string(),
+                    cells: [cell(old, name, &[]), None],
+                });
+            }
+        }
+        for name in diff.added.iter() {
+            rows.push(DiffRow { name: name.clone(), status: "added".to_string(), cells: [None, cell(new, name, &[])] });
+        }
+
+        DiffTemplate { old_name, new_name, diff, rows }
+    }
+}
+
 #[derive(Default)]
 pub struct ListRoleFragment(pub Vec<RoleFragment>);
 
@@ -80,8 +495,7 @@ impl From<Vec<NodeFragment>> for ListRoleFragment {
 
         for node in node_fragments {
             //do we already have entries with the specific page?
-            if hm_fragments.contains_key(&node.role) {
-                let hm_fragments_nodes = hm_fragments.get_mut(&node.role).unwrap();
+            if let Some(hm_fragments_nodes) = hm_fragments.get_mut(&node.role) {
                 hm_fragments_nodes.push(node.clone());
             } else {
                 //insert page with first node
@@ -98,9 +512,10 @@ impl From<Vec<NodeFragment>> for ListRoleFragment {
          let mut role_fragments = Vec::<RoleFragment>::new();
  
          for rnode in sorted_rnodes {
-             let mut role_fragment = RoleFragment::default();
-             role_fragment.name = rnode.0;
-             role_fragment.nodes = rnode.1;
+             let role_fragment = RoleFragment {
+                 name: rnode.0,
+                 nodes: rnode.1,
+             };
              role_fragments.push(role_fragment);
          }
         // let role_fragment: Vec<RoleFragment> = Vec::new();
@@ -111,3 +526,34 @@ impl From<Vec<NodeFragment>> for ListRoleFragment {
         ListRoleFragment(role_fragments)
     }
 }
+
+#[test]
+fn test_node_form_keeps_unknown_values() {
+    let mut node = Node {
+        node_name: "cr_lt0001000000".to_string(),
+        cp_cost: 100,
+        node_type: NodeType::INVALID,
+        type_raw: 9,
+        stage: 0,
+        role: 10,
+        stage_role_raw: 0x0a,
+        ..Default::default()
+    };
+
+    //The form as rendered, only the cost was edited.
+    let template = NodeFormTemplate::new(NodeFragment::from(node.clone()));
+    let form = NodeForm {
+        cp_cost: 250,
+        node_value: node.node_value,
+        node_type: template.types[0].clone(),
+        stage: 0,
+        role: template.roles[0].clone(),
+    };
+    form.apply(&mut node).unwrap();
+
+    assert_eq!(node.cp_cost, 250);
+    assert_eq!((node.type_byte(), node.stage_role_byte()), (9, 0x0a));
+
+    let form = NodeForm { stage: 16, ..form };
+    assert!(form.apply(&mut node).is_err());
+}